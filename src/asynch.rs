@@ -0,0 +1,742 @@
+//! Async driver over [`embedded_hal_async::i2c::I2c`]
+//!
+//! Offers the same register level API as the blocking [`CAP1XXX`](crate::CAP1XXX);
+//! both drivers share the register encoding and only differ in how it is sent on the bus.
+//!
+//! ```ignore
+//! let mut cap = cap1xxx::asynch::CAP1XXX::detect(i2c, 0x2C).await?;
+//! cap.init().await?;
+//! cap.set_led_state(0, true).await?;
+//! ```
+use crate::config::*;
+use crate::consts::*;
+use crate::error::Error;
+use crate::registers::{self, Access, CalibrationStep};
+use crate::status;
+use crate::{
+    ChannelValues, ChipModel, DeviceInfo, GeneralStatus, InputDiagnostics, PowerState, RWResult,
+    Status,
+};
+use core::time::Duration;
+use embedded_hal::digital::Error as _;
+use embedded_hal_async::delay::DelayNs;
+use embedded_hal_async::digital::Wait;
+use embedded_hal_async::i2c::I2c;
+
+pub struct CAP1XXX<T>
+where
+    T: I2c,
+{
+    i2c: T,
+    number_of_leds: u8,
+    number_of_channels: u8,
+    i2c_address: u8,
+    model: Option<ChipModel>,
+}
+
+impl<T> CAP1XXX<T>
+where
+    T: I2c,
+{
+    /// Create a driver for a device with as many inputs as LEDs, at most 8
+    pub fn new(i2c: T, i2c_address: u8, number_of_leds: u8) -> Self {
+        let number_of_leds = number_of_leds.min(8);
+        Self {
+            i2c,
+            number_of_leds,
+            number_of_channels: number_of_leds,
+            i2c_address,
+            model: None,
+        }
+    }
+
+    /// See [`CAP1XXX::detect`](crate::CAP1XXX::detect)
+    pub async fn detect(i2c: T, i2c_address: u8) -> RWResult<T, Self> {
+        let mut cap = Self::new(i2c, i2c_address, 0);
+        let model = cap.probe().await?.model;
+        cap.number_of_leds = model.led_count();
+        cap.number_of_channels = model.channel_count();
+        cap.model = Some(model);
+        Ok(cap)
+    }
+
+    /// See [`CAP1XXX::probe`](crate::CAP1XXX::probe)
+    pub async fn probe(&mut self) -> RWResult<T, DeviceInfo> {
+        let mut ids = [0u8; registers::ID_LEN];
+        self.read_block(registers::ID_START, &mut ids).await?;
+        registers::device_info(&ids)
+    }
+
+    /// See [`CAP1XXX::model`](crate::CAP1XXX::model)
+    pub fn model(&self) -> Option<ChipModel> {
+        self.model
+    }
+    pub fn number_of_leds(&self) -> u8 {
+        self.number_of_leds
+    }
+    pub fn number_of_channels(&self) -> u8 {
+        self.number_of_channels
+    }
+
+    /// Release the bus
+    pub fn release(self) -> T {
+        self.i2c
+    }
+
+    /// See [`CAP1XXX::init`](crate::CAP1XXX::init)
+    pub async fn init(&mut self) -> RWResult<T, ()> {
+        self.init_with(&Config::default()).await
+    }
+
+    pub async fn init_with(&mut self, config: &Config) -> RWResult<T, ()> {
+        for access in registers::init_sequence(config).iter() {
+            self.access(*access).await?;
+        }
+        Ok(())
+    }
+
+    async fn write_byte(&mut self, register: u8, value: u8) -> RWResult<T, ()> {
+        self.write_block(register, &[value]).await
+    }
+
+    /// Write consecutive registers in a single transaction
+    async fn write_block(&mut self, register: u8, values: &[u8]) -> RWResult<T, ()> {
+        let block = registers::Block::new(register, values);
+        self.i2c.write(self.i2c_address, block.as_bytes()).await?;
+        Ok(())
+    }
+
+    async fn read_byte(&mut self, register: u8) -> RWResult<T, u8> {
+        let mut buf = [0u8];
+        self.read_block(register, &mut buf).await?;
+        Ok(buf[0])
+    }
+
+    async fn read_block(&mut self, register: u8, buf: &mut [u8]) -> RWResult<T, ()> {
+        self.i2c
+            .write_read(self.i2c_address, &[register], buf)
+            .await?;
+        Ok(())
+    }
+
+    async fn access(&mut self, access: Access) -> RWResult<T, ()> {
+        match access {
+            Access::Write { register, value } => self.write_byte(register, value).await,
+            Access::Modify {
+                register,
+                mask,
+                value,
+            } => {
+                let current = self.read_byte(register).await?;
+                self.write_byte(register, Access::modified(mask, value, current))
+                    .await
+            }
+        }
+    }
+
+    // ----------------------------------------------------------------------------
+    // Buttons handling
+
+    /// See [`CAP1XXX::clear_interrupt`](crate::CAP1XXX::clear_interrupt)
+    pub async fn clear_interrupt(&mut self) -> RWResult<T, ()> {
+        self.access(registers::clear_interrupt()).await
+    }
+    pub async fn is_interrupted(&mut self) -> RWResult<T, bool> {
+        Ok(registers::interrupted(
+            self.read_byte(R_MAIN_CONTROL).await?,
+        ))
+    }
+    /// See [`CAP1XXX::wait_for_touch`](crate::CAP1XXX::wait_for_touch), the ALERT pin is
+    /// awaited with [`Wait`] instead of being polled.
+    pub async fn wait_for_touch<P>(&mut self, alert: &mut P) -> RWResult<T, u8>
+    where
+        P: Wait,
+    {
+        let asserted = if self.is_alert_active_low().await? {
+            alert.wait_for_low().await
+        } else {
+            alert.wait_for_high().await
+        };
+        asserted.map_err(|e| Error::AlertPinError(e.kind()))?;
+        let status = self.read_input_status().await?;
+        self.clear_interrupt().await?;
+        Ok(status)
+    }
+    /// See [`CAP1XXX::set_alert_active_low`](crate::CAP1XXX::set_alert_active_low)
+    pub async fn set_alert_active_low(&mut self, active_low: bool) -> RWResult<T, ()> {
+        self.access(registers::alert_polarity(active_low)).await
+    }
+    pub async fn is_alert_active_low(&mut self) -> RWResult<T, bool> {
+        Ok(registers::alert_active_low(
+            self.read_byte(R_CONFIGURATION2).await?,
+        ))
+    }
+    pub async fn auto_recalibrate(&mut self, value: bool) -> RWResult<T, ()> {
+        self.access(registers::auto_recalibrate(value)).await
+    }
+    /// See [`CAP1XXX::calibrate`](crate::CAP1XXX::calibrate)
+    pub async fn calibrate(&mut self, channels: u8) -> RWResult<T, ()> {
+        self.access(registers::calibrate(channels, self.number_of_channels))
+            .await
+    }
+    /// See [`CAP1XXX::wait_calibration_done`](crate::CAP1XXX::wait_calibration_done)
+    pub async fn wait_calibration_done<D: DelayNs>(
+        &mut self,
+        channels: u8,
+        timeout: Duration,
+        delay: &mut D,
+    ) -> RWResult<T, u8> {
+        let mut wait = registers::CalibrationWait::new(channels, timeout);
+        loop {
+            let general = self.read_general_status().await?;
+            let pending = self.read_byte(R_CALIBRATION).await?;
+            let out_of_limit = if general.base_count_out_of_limit {
+                self.read_byte(R_COUNT_O_LIMIT).await?
+            } else {
+                0
+            };
+            match wait.step(&general, pending, out_of_limit)? {
+                CalibrationStep::Done(failed) => return Ok(failed),
+                CalibrationStep::Wait(ms) => delay.delay_ms(ms).await,
+            }
+        }
+    }
+    pub async fn filter_analog_noise(&mut self, value: bool) -> RWResult<T, ()> {
+        self.access(registers::analog_noise_filter(value)).await
+    }
+    pub async fn filter_digital_noise(&mut self, value: bool) -> RWResult<T, ()> {
+        self.access(registers::digital_noise_filter(value)).await
+    }
+    /// See [`CAP1XXX::set_hold_delay`](crate::CAP1XXX::set_hold_delay)
+    pub async fn set_hold_delay(&mut self, delay: Duration) -> RWResult<T, ()> {
+        self.access(registers::hold_delay(delay)).await
+    }
+    /// See [`CAP1XXX::set_repeat_rate`](crate::CAP1XXX::set_repeat_rate)
+    pub async fn set_repeat_rate(&mut self, delay: Duration) -> RWResult<T, ()> {
+        self.access(registers::repeat_rate(delay)).await
+    }
+    /// See [`CAP1XXX::get_hold_delay`](crate::CAP1XXX::get_hold_delay)
+    pub async fn get_hold_delay(&mut self) -> RWResult<T, Duration> {
+        let value = self.read_byte(R_INPUT_CONFIG2).await?;
+        Ok(registers::rate_scale_to_duration(value))
+    }
+    /// See [`CAP1XXX::get_repeat_rate`](crate::CAP1XXX::get_repeat_rate)
+    pub async fn get_repeat_rate(&mut self) -> RWResult<T, Duration> {
+        let value = self.read_byte(R_INPUT_CONFIG).await?;
+        Ok(registers::rate_scale_to_duration(value))
+    }
+    pub async fn get_product_id(&mut self) -> RWResult<T, u8> {
+        self.read_byte(R_PRODUCT_ID).await
+    }
+    pub async fn get_manufacturer_id(&mut self) -> RWResult<T, u8> {
+        self.read_byte(R_MANUFACTURER_ID).await
+    }
+    pub async fn get_revision(&mut self) -> RWResult<T, u8> {
+        self.read_byte(R_REVISION).await
+    }
+
+    /// See [`CAP1XXX::enable_multitouch`](crate::CAP1XXX::enable_multitouch)
+    pub async fn enable_multitouch(&mut self, enable: bool) -> RWResult<T, ()> {
+        self.access(registers::multitouch(enable)).await
+    }
+    /// See [`CAP1XXX::set_touch_limit`](crate::CAP1XXX::set_touch_limit)
+    pub async fn set_touch_limit(&mut self, limit: TouchLimit) -> RWResult<T, ()> {
+        self.access(registers::touch_limit(limit)).await
+    }
+    pub async fn get_touch_limit(&mut self) -> RWResult<T, TouchLimit> {
+        Ok(registers::touch_limit_from(
+            self.read_byte(R_MTOUCH_CONFIG).await?,
+        ))
+    }
+    /// See [`CAP1XXX::is_multitouch_blocked`](crate::CAP1XXX::is_multitouch_blocked)
+    pub async fn is_multitouch_blocked(&mut self) -> RWResult<T, bool> {
+        Ok(self.read_general_status().await?.multitouch_blocked)
+    }
+    /// See [`CAP1XXX::configure_multiple_touch_pattern`](crate::CAP1XXX::configure_multiple_touch_pattern)
+    pub async fn configure_multiple_touch_pattern(
+        &mut self,
+        pattern: &MultipleTouchPattern,
+    ) -> RWResult<T, ()> {
+        for access in registers::multiple_touch_pattern(pattern).iter() {
+            self.access(*access).await?;
+        }
+        Ok(())
+    }
+    pub async fn get_multiple_touch_pattern(&mut self) -> RWResult<T, MultipleTouchPattern> {
+        Ok(MultipleTouchPattern::from_registers([
+            self.read_byte(R_MTOUCH_PAT_CONF).await?,
+            self.read_byte(R_MTOUCH_PATTERN).await?,
+        ]))
+    }
+    /// See [`CAP1XXX::is_multiple_touch_pattern_detected`](crate::CAP1XXX::is_multiple_touch_pattern_detected)
+    pub async fn is_multiple_touch_pattern_detected(&mut self) -> RWResult<T, bool> {
+        Ok(self.read_general_status().await?.pattern_detected)
+    }
+    pub async fn enable_repeat(&mut self, inputs: u8) -> RWResult<T, ()> {
+        self.write_byte(R_REPEAT_EN, inputs).await
+    }
+    /// See [`CAP1XXX::get_repeat_enabled`](crate::CAP1XXX::get_repeat_enabled)
+    pub async fn get_repeat_enabled(&mut self) -> RWResult<T, u8> {
+        self.read_byte(R_REPEAT_EN).await
+    }
+    pub async fn enable_interrupts(&mut self, inputs: u8) -> RWResult<T, ()> {
+        self.write_byte(R_INTERRUPT_EN, inputs).await
+    }
+    /// See [`CAP1XXX::get_interrupts_enabled`](crate::CAP1XXX::get_interrupts_enabled)
+    pub async fn get_interrupts_enabled(&mut self) -> RWResult<T, u8> {
+        self.read_byte(R_INTERRUPT_EN).await
+    }
+    pub async fn enable_inputs(&mut self, inputs: u8) -> RWResult<T, ()> {
+        self.write_byte(R_INPUT_ENABLE, inputs).await
+    }
+    /// See [`CAP1XXX::get_inputs_enabled`](crate::CAP1XXX::get_inputs_enabled)
+    pub async fn get_inputs_enabled(&mut self) -> RWResult<T, u8> {
+        self.read_byte(R_INPUT_ENABLE).await
+    }
+
+    pub async fn read_input_status(&mut self) -> RWResult<T, u8> {
+        self.read_byte(R_INPUT_STATUS).await
+    }
+    pub async fn read_general_status(&mut self) -> RWResult<T, GeneralStatus> {
+        Ok(GeneralStatus::from_register(
+            self.read_byte(R_GENERAL_STATUS).await?,
+        ))
+    }
+    /// See [`CAP1XXX::read_noise_flags`](crate::CAP1XXX::read_noise_flags)
+    pub async fn read_noise_flags(&mut self) -> RWResult<T, u8> {
+        self.read_byte(R_NOISE_FLAG_STATUS).await
+    }
+    /// See [`CAP1XXX::read_status`](crate::CAP1XXX::read_status)
+    pub async fn read_status(&mut self) -> RWResult<T, Status> {
+        let mut registers = [0u8; status::STATUS_LEN];
+        self.read_block(status::STATUS_START, &mut registers)
+            .await?;
+        Ok(Status::from_registers(&registers))
+    }
+
+    pub async fn read_threshold_values(&mut self) -> RWResult<T, ChannelValues<u8>> {
+        let mut values = ChannelValues::new(self.number_of_channels);
+        self.read_block(R_INPUT_1_THRESH, &mut values).await?;
+        Ok(values)
+    }
+    /// See [`CAP1XXX::set_threshold`](crate::CAP1XXX::set_threshold)
+    pub async fn set_threshold(&mut self, channel: u8, threshold: u8) -> RWResult<T, ()> {
+        let access = registers::threshold(channel, threshold, self.number_of_channels)?;
+        self.access(access).await
+    }
+    /// See [`CAP1XXX::set_thresholds`](crate::CAP1XXX::set_thresholds)
+    pub async fn set_thresholds(&mut self, thresholds: &[u8]) -> RWResult<T, ()> {
+        let values = registers::thresholds(thresholds, self.number_of_channels)?;
+        self.write_block(R_INPUT_1_THRESH, &values).await
+    }
+    /// See [`CAP1XXX::link_thresholds`](crate::CAP1XXX::link_thresholds)
+    pub async fn link_thresholds(&mut self, linked: bool) -> RWResult<T, ()> {
+        self.access(registers::linked_thresholds(linked)).await
+    }
+    pub async fn set_noise_threshold(&mut self, threshold: NoiseThreshold) -> RWResult<T, ()> {
+        self.access(registers::noise_threshold(threshold)).await
+    }
+    pub async fn get_noise_threshold(&mut self) -> RWResult<T, NoiseThreshold> {
+        Ok(NoiseThreshold::from_bits(
+            self.read_byte(R_NOISE_THRESH).await?,
+        ))
+    }
+
+    pub async fn set_sampling_config(&mut self, config: &SamplingConfig) -> RWResult<T, ()> {
+        self.write_byte(R_SAMPLING_CONFIG, config.to_register())
+            .await
+    }
+    pub async fn get_sampling_config(&mut self) -> RWResult<T, SamplingConfig> {
+        Ok(SamplingConfig::from_register(
+            self.read_byte(R_SAMPLING_CONFIG).await?,
+        ))
+    }
+
+    pub async fn set_sensitivity(&mut self, sensitivity: Sensitivity) -> RWResult<T, ()> {
+        self.access(registers::sensitivity(sensitivity)).await
+    }
+    pub async fn get_sensitivity(&mut self) -> RWResult<T, Sensitivity> {
+        Ok(registers::sensitivity_from(
+            self.read_byte(R_SENSITIVITY).await?,
+        ))
+    }
+    pub async fn set_base_shift(&mut self, base_shift: BaseShift) -> RWResult<T, ()> {
+        self.access(registers::base_shift(base_shift)).await
+    }
+    pub async fn get_base_shift(&mut self) -> RWResult<T, BaseShift> {
+        Ok(BaseShift::from_bits(self.read_byte(R_SENSITIVITY).await?))
+    }
+
+    /// See [`CAP1XXX::read_delta_values`](crate::CAP1XXX::read_delta_values)
+    pub async fn read_delta_values(&mut self) -> RWResult<T, ChannelValues<i8>> {
+        let mut values = ChannelValues::new(self.number_of_channels);
+        self.read_block(R_INPUT_1_DELTA, &mut values).await?;
+        Ok(registers::deltas(&values))
+    }
+
+    /// See [`CAP1XXX::read_calibration_values`](crate::CAP1XXX::read_calibration_values)
+    pub async fn read_calibration_values(&mut self) -> RWResult<T, ChannelValues<u16>> {
+        let mut block = [0u8; registers::CALIBRATION_LEN];
+        self.read_block(registers::CALIBRATION_START, &mut block)
+            .await?;
+        Ok(registers::calibration_values(
+            &block,
+            self.number_of_channels,
+        ))
+    }
+    /// See [`CAP1XXX::read_base_counts`](crate::CAP1XXX::read_base_counts)
+    pub async fn read_base_counts(&mut self) -> RWResult<T, ChannelValues<u8>> {
+        let mut values = ChannelValues::new(self.number_of_channels);
+        self.read_block(R_INPUT_1_BCOUNT, &mut values).await?;
+        Ok(values)
+    }
+    /// See [`CAP1XXX::read_diagnostics`](crate::CAP1XXX::read_diagnostics)
+    pub async fn read_diagnostics(&mut self) -> RWResult<T, ChannelValues<InputDiagnostics>> {
+        let calibrations = self.read_calibration_values().await?;
+        let base_counts = self.read_base_counts().await?;
+        Ok(registers::diagnostics(&calibrations, &base_counts))
+    }
+
+    // ----------------------------------------------------------------------------
+    // Power management
+
+    pub async fn get_power_state(&mut self) -> RWResult<T, PowerState> {
+        Ok(PowerState::from_main_control(
+            self.read_byte(R_MAIN_CONTROL).await?,
+        ))
+    }
+    /// See [`CAP1XXX::enter_deep_sleep`](crate::CAP1XXX::enter_deep_sleep)
+    pub async fn enter_deep_sleep(&mut self) -> RWResult<T, ()> {
+        self.access(registers::deep_sleep(true)).await
+    }
+    pub async fn exit_deep_sleep(&mut self) -> RWResult<T, ()> {
+        self.access(registers::deep_sleep(false)).await
+    }
+    /// See [`CAP1XXX::enter_standby`](crate::CAP1XXX::enter_standby)
+    pub async fn enter_standby(&mut self) -> RWResult<T, ()> {
+        self.access(registers::standby(true)).await
+    }
+    /// See [`CAP1XXX::exit_standby`](crate::CAP1XXX::exit_standby)
+    pub async fn exit_standby(&mut self) -> RWResult<T, ()> {
+        self.access(registers::standby(false)).await
+    }
+    pub async fn is_in_standby(&mut self) -> RWResult<T, bool> {
+        Ok(registers::in_standby(self.read_byte(R_MAIN_CONTROL).await?))
+    }
+    /// See [`CAP1XXX::assert_wake_on_standby_touch`](crate::CAP1XXX::assert_wake_on_standby_touch)
+    pub async fn assert_wake_on_standby_touch(&mut self, value: bool) -> RWResult<T, ()> {
+        self.access(registers::wake_on_standby_touch(value)).await
+    }
+    /// See [`CAP1XXX::configure_standby`](crate::CAP1XXX::configure_standby)
+    pub async fn configure_standby(&mut self, config: &StandbyConfig) -> RWResult<T, ()> {
+        self.write_block(R_STANDBY_CHANNEL, &config.to_registers())
+            .await
+    }
+    pub async fn get_standby_config(&mut self) -> RWResult<T, StandbyConfig> {
+        let mut registers = [0u8; 4];
+        self.read_block(R_STANDBY_CHANNEL, &mut registers).await?;
+        Ok(StandbyConfig::from_registers(registers))
+    }
+
+    // ----------------------------------------------------------------------------
+    // LEDS handling
+    pub async fn set_led_linking(&mut self, led_index: u8, state: bool) -> RWResult<T, ()> {
+        let access = registers::led_bit(R_LED_LINKING, led_index, state, self.number_of_leds)?;
+        self.access(access).await
+    }
+    pub async fn set_led_output_type(&mut self, led_index: u8, state: bool) -> RWResult<T, ()> {
+        let access = registers::led_bit(R_LED_OUTPUT_TYPE, led_index, state, self.number_of_leds)?;
+        self.access(access).await
+    }
+    pub async fn set_led_state(&mut self, led_index: u8, state: bool) -> RWResult<T, ()> {
+        let access = registers::led_bit(R_LED_OUTPUT_CON, led_index, state, self.number_of_leds)?;
+        self.access(access).await
+    }
+    pub async fn set_led_polarity(&mut self, led_index: u8, state: bool) -> RWResult<T, ()> {
+        let access = registers::led_bit(R_LED_POLARITY, led_index, state, self.number_of_leds)?;
+        self.access(access).await
+    }
+    /// See [`CAP1XXX::set_led_states`](crate::CAP1XXX::set_led_states)
+    pub async fn set_led_states(&mut self, states: u8) -> RWResult<T, ()> {
+        self.access(registers::led_states(states, self.number_of_leds))
+            .await
+    }
+    /// See [`CAP1XXX::set_led_behaviour`](crate::CAP1XXX::set_led_behaviour)
+    pub async fn set_led_behaviour(&mut self, led_index: u8, value: u8) -> RWResult<T, ()> {
+        let access = registers::led_behaviour(led_index, value, self.number_of_leds)?;
+        self.access(access).await
+    }
+
+    /// See [`CAP1XXX::set_led_pulse1_period`](crate::CAP1XXX::set_led_pulse1_period)
+    pub async fn set_led_pulse1_period(&mut self, period: Duration) -> RWResult<T, ()> {
+        self.access(registers::led_period(R_LED_PULSE_1_PER, period))
+            .await
+    }
+    /// See [`CAP1XXX::set_led_pulse2_period`](crate::CAP1XXX::set_led_pulse2_period)
+    pub async fn set_led_pulse2_period(&mut self, period: Duration) -> RWResult<T, ()> {
+        self.access(registers::led_period(R_LED_PULSE_2_PER, period))
+            .await
+    }
+    pub async fn set_led_breathe_period(&mut self, period: Duration) -> RWResult<T, ()> {
+        self.access(registers::led_period(R_LED_BREATHE_PER, period))
+            .await
+    }
+    /// See [`CAP1XXX::set_led_pulse1_count`](crate::CAP1XXX::set_led_pulse1_count)
+    pub async fn set_led_pulse1_count(&mut self, count: u8) -> RWResult<T, ()> {
+        self.access(registers::led_pulse_count(0, count)?).await
+    }
+    /// See [`CAP1XXX::set_led_pulse2_count`](crate::CAP1XXX::set_led_pulse2_count)
+    pub async fn set_led_pulse2_count(&mut self, count: u8) -> RWResult<T, ()> {
+        self.access(registers::led_pulse_count(3, count)?).await
+    }
+    pub async fn set_led_ramp_alert(&mut self, value: bool) -> RWResult<T, ()> {
+        self.access(registers::led_ramp_alert(value)).await
+    }
+    /// See [`CAP1XXX::set_led_direct_ramp_rate`](crate::CAP1XXX::set_led_direct_ramp_rate)
+    pub async fn set_led_direct_ramp_rate(
+        &mut self,
+        rise_rate: u16,
+        fall_rate: u16,
+    ) -> RWResult<T, ()> {
+        self.access(registers::led_direct_ramp_rate(rise_rate, fall_rate))
+            .await
+    }
+    pub async fn set_led_direct_duty(&mut self, duty_min: u8, duty_max: u8) -> RWResult<T, ()> {
+        self.access(registers::led_duty(R_LED_DIRECT_DUT, duty_min, duty_max))
+            .await
+    }
+    pub async fn set_led_pulse1_duty(&mut self, duty_min: u8, duty_max: u8) -> RWResult<T, ()> {
+        self.access(registers::led_duty(R_LED_PULSE_1_DUT, duty_min, duty_max))
+            .await
+    }
+    pub async fn set_led_pulse2_duty(&mut self, duty_min: u8, duty_max: u8) -> RWResult<T, ()> {
+        self.access(registers::led_duty(R_LED_PULSE_2_DUT, duty_min, duty_max))
+            .await
+    }
+    pub async fn set_led_breathe_duty(&mut self, duty_min: u8, duty_max: u8) -> RWResult<T, ()> {
+        self.access(registers::led_duty(R_LED_BREATHE_DUT, duty_min, duty_max))
+            .await
+    }
+    /// See [`CAP1XXX::set_led_duties`](crate::CAP1XXX::set_led_duties)
+    pub async fn set_led_duties(&mut self, duties: &LedDuties) -> RWResult<T, ()> {
+        self.write_block(R_LED_PULSE_1_DUT, &duties.to_registers())
+            .await
+    }
+    pub async fn get_led_duties(&mut self) -> RWResult<T, LedDuties> {
+        let mut registers = [0u8; 4];
+        self.read_block(R_LED_PULSE_1_DUT, &mut registers).await?;
+        Ok(LedDuties::from_registers(registers))
+    }
+    pub async fn set_led_direct_min_duty(&mut self, value: u8) -> RWResult<T, ()> {
+        self.access(registers::led_min_duty(R_LED_DIRECT_DUT, value))
+            .await
+    }
+    pub async fn set_led_direct_max_duty(&mut self, value: u8) -> RWResult<T, ()> {
+        self.access(registers::led_max_duty(R_LED_DIRECT_DUT, value))
+            .await
+    }
+    pub async fn set_led_breathe_min_duty(&mut self, value: u8) -> RWResult<T, ()> {
+        self.access(registers::led_min_duty(R_LED_BREATHE_DUT, value))
+            .await
+    }
+    pub async fn set_led_breathe_max_duty(&mut self, value: u8) -> RWResult<T, ()> {
+        self.access(registers::led_max_duty(R_LED_BREATHE_DUT, value))
+            .await
+    }
+    pub async fn set_led_pulse1_min_duty(&mut self, value: u8) -> RWResult<T, ()> {
+        self.access(registers::led_min_duty(R_LED_PULSE_1_DUT, value))
+            .await
+    }
+    pub async fn set_led_pulse1_max_duty(&mut self, value: u8) -> RWResult<T, ()> {
+        self.access(registers::led_max_duty(R_LED_PULSE_1_DUT, value))
+            .await
+    }
+    pub async fn set_led_pulse2_min_duty(&mut self, value: u8) -> RWResult<T, ()> {
+        self.access(registers::led_min_duty(R_LED_PULSE_2_DUT, value))
+            .await
+    }
+    pub async fn set_led_pulse2_max_duty(&mut self, value: u8) -> RWResult<T, ()> {
+        self.access(registers::led_max_duty(R_LED_PULSE_2_DUT, value))
+            .await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mock::Emulator;
+    use embassy_futures::block_on;
+
+    struct NoDelay;
+
+    impl DelayNs for NoDelay {
+        async fn delay_ns(&mut self, _ns: u32) {}
+    }
+
+    #[test]
+    fn detect_and_init() {
+        let emulator = Emulator::new();
+        let mut cap = block_on(CAP1XXX::detect(emulator.clone(), 0x28)).unwrap();
+        assert_eq!(cap.model(), Some(ChipModel::Cap1166));
+        assert_eq!(block_on(cap.probe()).unwrap().revision, 0x83);
+        block_on(cap.init()).unwrap();
+        assert_eq!(block_on(cap.get_inputs_enabled()).unwrap(), 0xFF);
+        assert_eq!(
+            block_on(cap.get_hold_delay()).unwrap(),
+            Duration::from_millis(210)
+        );
+        assert_eq!(emulator.register(R_CONFIGURATION2), 0b01100000);
+    }
+
+    #[test]
+    fn at_most_8_inputs() {
+        let mut cap = CAP1XXX::new(Emulator::new(), 0x28, 9);
+        assert_eq!(cap.number_of_channels(), 8);
+        assert_eq!(block_on(cap.read_threshold_values()).unwrap().len(), 8);
+        assert_eq!(block_on(cap.read_calibration_values()).unwrap().len(), 8);
+    }
+
+    #[test]
+    fn calibration_completion() {
+        let emulator = Emulator::new();
+        let mut cap = CAP1XXX::new(emulator.clone(), 0x28, 6);
+        let timeout = Duration::from_millis(50);
+        emulator.fail_calibration(0b100);
+        block_on(cap.calibrate(0b110)).unwrap();
+        assert_eq!(
+            block_on(cap.wait_calibration_done(0b110, timeout, &mut NoDelay)).unwrap(),
+            0b100
+        );
+        assert_eq!(emulator.register(R_CALIBRATION), 0b100);
+
+        emulator.set_register(R_INPUT_3_CALIB, 0x20);
+        emulator.set_register(R_INPUT_CAL_LSB1, 0b11_0000);
+        emulator.set_register(R_INPUT_3_BCOUNT, 0x55);
+        let diagnostics = block_on(cap.read_diagnostics()).unwrap();
+        assert_eq!(diagnostics.len(), 6);
+        assert_eq!(
+            diagnostics[2],
+            InputDiagnostics {
+                calibration: 0x83,
+                base_count: 0x55
+            }
+        );
+    }
+
+    #[test]
+    fn inputs_and_thresholds() {
+        let emulator = Emulator::new();
+        let mut cap = CAP1XXX::new(emulator.clone(), 0x28, 6);
+        block_on(cap.init()).unwrap();
+
+        emulator.press(2);
+        assert!(block_on(cap.is_interrupted()).unwrap());
+        assert_eq!(block_on(cap.read_input_status()).unwrap(), 0b100);
+        let status = block_on(cap.read_status()).unwrap();
+        assert_eq!(status.inputs, 0b100);
+        assert!(status.general.touch);
+        block_on(cap.clear_interrupt()).unwrap();
+        assert!(!block_on(cap.is_interrupted()).unwrap());
+
+        block_on(cap.set_thresholds(&[1, 2, 3])).unwrap();
+        assert_eq!(
+            block_on(cap.read_threshold_values()).unwrap()[..],
+            [1, 2, 3, 1, 1, 1]
+        );
+        assert!(matches!(
+            block_on(cap.set_threshold(6, 10)),
+            Err(Error::ChannelNumberOverflowError)
+        ));
+
+        let pattern = MultipleTouchPattern {
+            enabled: true,
+            mode: PatternMode::Count,
+            threshold: PatternThreshold::Percent100,
+            alert: false,
+            inputs: 0b11,
+        };
+        block_on(cap.configure_multiple_touch_pattern(&pattern)).unwrap();
+        assert_eq!(block_on(cap.get_multiple_touch_pattern()).unwrap(), pattern);
+        assert!(!block_on(cap.is_multiple_touch_pattern_detected()).unwrap());
+        emulator.press(3);
+        assert!(block_on(cap.is_multiple_touch_pattern_detected()).unwrap());
+    }
+
+    #[test]
+    fn wait_for_touch() {
+        let emulator = Emulator::new();
+        let mut cap = CAP1XXX::new(emulator.clone(), 0x28, 6);
+        block_on(cap.init()).unwrap();
+        let mut alert = emulator.alert_pin();
+        emulator.press(4);
+        assert_eq!(block_on(cap.wait_for_touch(&mut alert)).unwrap(), 0b10000);
+        assert!(!emulator.is_alert_asserted());
+
+        block_on(cap.set_alert_active_low(false)).unwrap();
+        emulator.release(4);
+        // still latched until the interrupt is cleared
+        assert_eq!(block_on(cap.wait_for_touch(&mut alert)).unwrap(), 0b10000);
+        assert_eq!(block_on(cap.read_input_status()).unwrap(), 0);
+    }
+
+    #[test]
+    fn leds() {
+        let emulator = Emulator::new();
+        let mut cap = CAP1XXX::new(emulator.clone(), 0x28, 6);
+        block_on(cap.set_led_state(3, true)).unwrap();
+        block_on(cap.set_led_behaviour(5, 0b10)).unwrap();
+        block_on(cap.set_led_pulse2_duty(2, 9)).unwrap();
+        assert_eq!(emulator.register(R_LED_OUTPUT_CON), 0b1000);
+        assert_eq!(emulator.register(R_LED_BEHAVIOUR_2) & 0b1100, 0b1000);
+        assert_eq!(emulator.register(R_LED_PULSE_2_DUT), 0x92);
+        assert!(matches!(
+            block_on(cap.set_led_state(6, true)),
+            Err(Error::LedNumberOverflowError)
+        ));
+
+        block_on(cap.set_led_pulse2_min_duty(5)).unwrap();
+        block_on(cap.set_led_breathe_max_duty(0xC)).unwrap();
+        block_on(cap.set_led_direct_min_duty(1)).unwrap();
+        assert_eq!(emulator.register(R_LED_PULSE_2_DUT), 0x95);
+        let duties = block_on(cap.get_led_duties()).unwrap();
+        assert_eq!(
+            duties,
+            LedDuties::from_registers([
+                emulator.register(R_LED_PULSE_1_DUT),
+                0x95,
+                emulator.register(R_LED_BREATHE_DUT),
+                emulator.register(R_LED_DIRECT_DUT),
+            ])
+        );
+        assert_eq!(emulator.register(R_LED_BREATHE_DUT) >> 4, 0xC);
+        assert_eq!(emulator.register(R_LED_DIRECT_DUT) & 0x0F, 1);
+
+        block_on(cap.set_led_pulse1_count(8)).unwrap();
+        assert_eq!(emulator.register(R_LED_CONFIG) & 0b111, 0b111);
+        for &count in &[0, 9] {
+            assert!(matches!(
+                block_on(cap.set_led_pulse2_count(count)),
+                Err(Error::PulseCountError(c)) if c == count
+            ));
+        }
+    }
+
+    #[test]
+    fn standby() {
+        let emulator = Emulator::new();
+        let mut cap = CAP1XXX::new(emulator.clone(), 0x28, 6);
+        let config = StandbyConfig {
+            channels: 0b10,
+            threshold: 0x20,
+            ..StandbyConfig::default()
+        };
+        block_on(cap.configure_standby(&config)).unwrap();
+        assert_eq!(emulator.register(R_STANDBY_CHANNEL), 0b10);
+        assert_eq!(block_on(cap.get_standby_config()).unwrap(), config);
+        assert!(!block_on(cap.is_in_standby()).unwrap());
+        block_on(cap.enter_standby()).unwrap();
+        assert!(block_on(cap.is_in_standby()).unwrap());
+        block_on(cap.assert_wake_on_standby_touch(true)).unwrap();
+        assert_eq!(emulator.register(R_GENERAL_CONFIG) & 0x40, 0x40);
+    }
+}
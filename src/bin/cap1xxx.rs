@@ -0,0 +1,374 @@
+//! Command-line tool driving a cap1xxx device on a Linux i2c-dev bus
+//!
+//! ```text
+//! cap1xxx --bus /dev/i2c-1 --address 0x2c status
+//! cap1xxx set-led 2 on
+//! cap1xxx watch
+//! ```
+use cap1xxx::config::{LedBehaviour, Sensitivity};
+use cap1xxx::{GeneralStatus, TouchDecoder, CAP1XXX};
+use clap::{Parser, Subcommand, ValueEnum};
+use embedded_hal::delay::DelayNs;
+use embedded_hal::i2c::I2c;
+use linux_embedded_hal::I2cdev;
+use std::error::Error;
+use std::io::{self, Write};
+use std::process;
+use std::thread;
+use std::time::{Duration, Instant};
+
+#[derive(Debug, Parser)]
+#[command(name = "cap1xxx", version, about = "Drive a cap1xxx touch controller")]
+struct Cli {
+    /// I2C bus device
+    #[arg(long, default_value = "/dev/i2c-1")]
+    bus: String,
+    /// Address of the device, decimal or 0x prefixed hexadecimal
+    #[arg(long, default_value = "0x2c", value_parser = parse_u8)]
+    address: u8,
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Debug, Subcommand)]
+enum Command {
+    /// Identify the device
+    Probe,
+    /// Show the power state, touched inputs, thresholds and deltas
+    Status,
+    /// Decode all the readable registers
+    Dump,
+    /// Show the calibration value and base count of each input
+    Diagnostics,
+    /// Turn a LED on or off
+    SetLed {
+        led: u8,
+        #[arg(value_enum)]
+        state: LedState,
+    },
+    /// Set the behaviour of a LED
+    LedBehaviour {
+        led: u8,
+        #[arg(value_enum)]
+        behaviour: Behaviour,
+    },
+    /// Set the touch threshold of an input
+    Threshold {
+        channel: u8,
+        #[arg(value_parser = clap::value_parser!(u8).range(0..=127))]
+        threshold: u8,
+    },
+    /// Show or set the sensitivity multiplier: 1, 2, 4, ... 128
+    Sensitivity {
+        #[arg(value_parser = parse_sensitivity)]
+        multiplier: Option<Sensitivity>,
+    },
+    /// Print touch events as they happen
+    Watch {
+        /// Polling interval in milliseconds
+        #[arg(long, default_value_t = 20)]
+        interval: u64,
+        /// Exit after this number of events
+        #[arg(long)]
+        count: Option<usize>,
+    },
+    /// Calibrate inputs and report those that failed
+    Calibrate {
+        /// Bitmap of the inputs to calibrate
+        #[arg(default_value = "0xff", value_parser = parse_u8)]
+        channels: u8,
+        /// Time to wait for the calibration to complete, in milliseconds
+        #[arg(long, default_value_t = 1000)]
+        timeout: u64,
+    },
+}
+
+#[derive(Debug, Clone, Copy, ValueEnum)]
+enum LedState {
+    On,
+    Off,
+}
+
+#[derive(Debug, Clone, Copy, ValueEnum)]
+enum Behaviour {
+    Direct,
+    Pulse1,
+    Pulse2,
+    Breathe,
+}
+
+impl From<Behaviour> for LedBehaviour {
+    fn from(behaviour: Behaviour) -> Self {
+        match behaviour {
+            Behaviour::Direct => LedBehaviour::Direct,
+            Behaviour::Pulse1 => LedBehaviour::Pulse1,
+            Behaviour::Pulse2 => LedBehaviour::Pulse2,
+            Behaviour::Breathe => LedBehaviour::Breathe,
+        }
+    }
+}
+
+fn parse_u8(value: &str) -> Result<u8, String> {
+    let parsed = match value.strip_prefix("0x") {
+        Some(hex) => u8::from_str_radix(hex, 16),
+        None => match value.strip_prefix("0b") {
+            Some(binary) => u8::from_str_radix(binary, 2),
+            None => value.parse(),
+        },
+    };
+    parsed.map_err(|e| e.to_string())
+}
+
+fn parse_sensitivity(value: &str) -> Result<Sensitivity, String> {
+    value
+        .parse()
+        .ok()
+        .and_then(Sensitivity::from_multiplier)
+        .ok_or_else(|| String::from("expected a power of 2 from 1 to 128"))
+}
+
+type CliResult<R> = Result<R, Box<dyn Error>>;
+
+/// Delay provider sleeping the current thread
+struct Sleep;
+
+impl DelayNs for Sleep {
+    fn delay_ns(&mut self, ns: u32) {
+        thread::sleep(Duration::from_nanos(u64::from(ns)));
+    }
+}
+
+/// Names of the flags set in the general status
+fn flags(status: &GeneralStatus) -> String {
+    let names: Vec<_> = [
+        (status.base_count_out_of_limit, "BC_OUT"),
+        (status.calibration_failed, "ACAL_FAIL"),
+        (status.power_button, "PWR"),
+        (status.led, "LED"),
+        (status.multitouch_blocked, "MULT"),
+        (status.pattern_detected, "MTP"),
+        (status.touch, "TOUCH"),
+    ]
+    .iter()
+    .filter(|(set, _)| *set)
+    .map(|(_, name)| *name)
+    .collect();
+    if names.is_empty() {
+        String::from("none")
+    } else {
+        names.join(" ")
+    }
+}
+
+fn run<T, W>(cap: &mut CAP1XXX<T>, command: &Command, out: &mut W) -> CliResult<()>
+where
+    T: I2c,
+    T::Error: Error + 'static,
+    W: Write,
+{
+    match command {
+        Command::Probe => {
+            match cap.model() {
+                Some(model) => writeln!(out, "model:           {:?}", model)?,
+                None => writeln!(out, "model:           unknown")?,
+            }
+            writeln!(out, "product id:      {:#04x}", cap.get_product_id()?)?;
+            writeln!(out, "manufacturer id: {:#04x}", cap.get_manufacturer_id()?)?;
+            writeln!(out, "revision:        {:#04x}", cap.get_revision()?)?;
+            writeln!(out, "inputs:          {}", cap.number_of_channels())?;
+            writeln!(out, "leds:            {}", cap.number_of_leds())?;
+        }
+        Command::Status => {
+            let channels = cap.number_of_channels() as usize;
+            writeln!(out, "power:       {:?}", cap.get_power_state()?)?;
+            writeln!(out, "interrupt:   {}", cap.is_interrupted()?)?;
+            let status = cap.read_status()?;
+            writeln!(
+                out,
+                "touched:     {:#0width$b}",
+                status.inputs,
+                width = channels + 2
+            )?;
+            writeln!(
+                out,
+                "noisy:       {:#0width$b}",
+                status.noise,
+                width = channels + 2
+            )?;
+            writeln!(out, "flags:       {}", flags(&status.general))?;
+            writeln!(out, "sensitivity: {}x", cap.get_sensitivity()?.multiplier())?;
+            let thresholds = cap.read_threshold_values()?;
+            let deltas = cap.read_delta_values()?;
+            writeln!(out, "input  threshold  delta")?;
+            for (channel, (threshold, delta)) in thresholds.iter().zip(deltas.iter()).enumerate() {
+                writeln!(out, "{:<5}  {:<9}  {}", channel, threshold, delta)?;
+            }
+        }
+        Command::Dump => write!(out, "{}", cap.snapshot()?)?,
+        Command::Diagnostics => {
+            writeln!(out, "input  calibration  base count")?;
+            for (channel, input) in cap.read_diagnostics()?.iter().enumerate() {
+                writeln!(
+                    out,
+                    "{:<5}  {:<11}  {}",
+                    channel, input.calibration, input.base_count
+                )?;
+            }
+        }
+        Command::SetLed { led, state } => cap.set_led_state(*led, matches!(state, LedState::On))?,
+        Command::LedBehaviour { led, behaviour } => {
+            cap.set_led_behaviour(*led, LedBehaviour::from(*behaviour).bits())?
+        }
+        Command::Threshold { channel, threshold } => cap.set_threshold(*channel, *threshold)?,
+        Command::Sensitivity { multiplier } => match multiplier {
+            Some(sensitivity) => cap.set_sensitivity(*sensitivity)?,
+            None => writeln!(out, "{}x", cap.get_sensitivity()?.multiplier())?,
+        },
+        Command::Watch { interval, count } => {
+            let mut decoder = TouchDecoder::from_device(cap)?;
+            let start = Instant::now();
+            let mut remaining = *count;
+            while remaining != Some(0) {
+                let now = start.elapsed();
+                let events = decoder.poll(cap, now)?;
+                for event in events.take(remaining.unwrap_or(usize::MAX)) {
+                    writeln!(
+                        out,
+                        "{:>10.3}  input {}  {:?}",
+                        now.as_secs_f64(),
+                        event.channel,
+                        event.kind
+                    )?;
+                    remaining = remaining.map(|remaining| remaining - 1);
+                }
+                out.flush()?;
+                if remaining != Some(0) {
+                    thread::sleep(Duration::from_millis(*interval));
+                }
+            }
+        }
+        Command::Calibrate { channels, timeout } => {
+            cap.clear_interrupt()?;
+            cap.calibrate(*channels)?;
+            let timeout = Duration::from_millis(*timeout);
+            let failed = cap.wait_calibration_done(*channels, timeout, &mut Sleep)?;
+            if failed == 0 {
+                writeln!(out, "calibrated")?;
+            } else {
+                writeln!(
+                    out,
+                    "failed:      {:#0width$b}",
+                    failed,
+                    width = cap.number_of_channels() as usize + 2
+                )?;
+            }
+        }
+    }
+    Ok(())
+}
+
+fn main() {
+    let cli = Cli::parse();
+    let result = I2cdev::new(&cli.bus)
+        .map_err(Box::<dyn Error>::from)
+        .and_then(|i2c| Ok(CAP1XXX::detect(i2c, cli.address)?))
+        .and_then(|mut cap| run(&mut cap, &cli.command, &mut io::stdout().lock()));
+    if let Err(e) = result {
+        eprintln!("cap1xxx: {}", e);
+        process::exit(1);
+    }
+}
+
+#[cfg(all(test, feature = "mock"))]
+mod tests {
+    use super::*;
+    use cap1xxx::mock::Emulator;
+
+    fn run_args(emulator: &Emulator, args: &[&str]) -> CliResult<String> {
+        let cli = Cli::try_parse_from(["cap1xxx"].iter().chain(args))?;
+        let mut cap = CAP1XXX::detect(emulator.clone(), 0x28)?;
+        let mut out = Vec::new();
+        run(&mut cap, &cli.command, &mut out)?;
+        Ok(String::from_utf8(out)?)
+    }
+
+    #[test]
+    fn probe_and_status() {
+        let emulator = Emulator::new();
+        let probe = run_args(&emulator, &["probe"]).unwrap();
+        assert!(probe.starts_with("model:           Cap1166\nproduct id:      0x51\n"));
+        assert!(probe.ends_with("inputs:          6\nleds:            6\n"));
+
+        emulator.press(2);
+        let status = run_args(&emulator, &["status"]).unwrap();
+        assert!(status.starts_with(
+            "power:       Active\ninterrupt:   true\ntouched:     0b000100\nnoisy:       0b000000\nflags:       TOUCH\nsensitivity: 32x\n"
+        ));
+        assert!(status.contains("input  threshold  delta\n0      64         0\n"));
+        assert_eq!(status.lines().count(), 13);
+        emulator.set_register(0x02, 0b0110_0101);
+        let status = run_args(&emulator, &["status"]).unwrap();
+        assert!(status.contains("flags:       BC_OUT ACAL_FAIL MULT TOUCH\n"));
+
+        let dump = run_args(&emulator, &["dump"]).unwrap();
+        assert!(dump.contains("INPUT_STATUS"));
+    }
+
+    #[test]
+    fn diagnostics() {
+        let emulator = Emulator::new();
+        // INPUT_2_CALIB, INPUT_CAL_LSB1, INPUT_2_BCOUNT
+        emulator.set_register(0xB2, 0x40);
+        emulator.set_register(0xB9, 0b0100);
+        emulator.set_register(0x51, 200);
+        let diagnostics = run_args(&emulator, &["diagnostics"]).unwrap();
+        let lines: Vec<_> = diagnostics.lines().collect();
+        assert_eq!(lines.len(), 7);
+        assert_eq!(lines[0], "input  calibration  base count");
+        assert_eq!(lines[1], "0      0            0");
+        assert_eq!(lines[2], "1      257          200");
+    }
+
+    #[test]
+    fn settings() {
+        let emulator = Emulator::new();
+        run_args(&emulator, &["set-led", "3", "on"]).unwrap();
+        // LED_OUTPUT_CONTROL
+        assert_eq!(emulator.register(0x74), 0b1000);
+        run_args(&emulator, &["led-behaviour", "1", "breathe"]).unwrap();
+        // LED_BEHAVIOUR_1
+        assert_eq!(emulator.register(0x81), 0b1100);
+        run_args(&emulator, &["--address", "0x28", "threshold", "0", "100"]).unwrap();
+        // INPUT_1_THRESHOLD
+        assert_eq!(emulator.register(0x30), 100);
+        run_args(&emulator, &["sensitivity", "8"]).unwrap();
+        assert_eq!(run_args(&emulator, &["sensitivity"]).unwrap(), "8x\n");
+        assert_eq!(
+            run_args(&emulator, &["calibrate", "0b11"]).unwrap(),
+            "calibrated\n"
+        );
+        // CALIBRATION_ACTIVATE
+        assert_eq!(emulator.register(0x26), 0);
+        emulator.fail_calibration(0b10);
+        assert_eq!(
+            run_args(&emulator, &["calibrate"]).unwrap(),
+            "failed:      0b000010\n"
+        );
+
+        assert!(run_args(&emulator, &["threshold", "0", "128"]).is_err());
+        assert!(run_args(&emulator, &["sensitivity", "3"]).is_err());
+        assert!(run_args(&emulator, &["set-led", "6", "on"]).is_err());
+    }
+
+    #[test]
+    fn watch() {
+        let emulator = Emulator::new();
+        emulator.press(1);
+        emulator.press(4);
+        let events = run_args(&emulator, &["watch", "--count", "2"]).unwrap();
+        let lines: Vec<_> = events.lines().map(str::trim_start).collect();
+        assert_eq!(lines.len(), 2);
+        assert!(lines[0].ends_with("  input 1  Press"));
+        assert!(lines[1].ends_with("  input 4  Press"));
+    }
+}
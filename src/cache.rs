@@ -0,0 +1,82 @@
+//! Shadow copy of the configuration registers
+use crate::consts::*;
+
+/// First and last registers of the block read by `sync_from_device`
+pub(crate) const CACHE_START: u8 = R_SENSITIVITY;
+pub(crate) const CACHE_END: u8 = R_LED_OFF_DELAY;
+pub(crate) const CACHE_LEN: usize = (CACHE_END - CACHE_START) as usize + 1;
+
+/// Registers only changed by the host, whose value can be kept by the driver
+///
+/// Registers updated by the device (MAIN_CONTROL, status, BASE_COUNT_OUT_OF_LIMIT,
+/// deltas, base counts, self-clearing CALIBRATION_ACTIVATE) or by side effects of other writes (linked
+/// thresholds) are never cached.
+pub(crate) fn is_cacheable(register: u8) -> bool {
+    matches!(
+        register,
+        R_SENSITIVITY..=R_SAMPLING_CONFIG
+        | R_INTERRUPT_EN
+        | R_REPEAT_EN
+        | R_MTOUCH_CONFIG..=R_MTOUCH_PAT_CONF
+        | R_MTOUCH_PATTERN
+        | R_RECALIBRATION
+        | R_NOISE_THRESH
+        | R_STANDBY_CHANNEL..=R_CONFIGURATION2
+        | R_POWER_BUTTON
+        | R_POW_BUTTON_CONF
+        | R_LED_OUTPUT_TYPE..=R_LED_OUTPUT_CON
+        | R_LED_LTRANS_CON
+        | R_LED_MIRROR_CON
+        | R_LED_BEHAVIOUR_1
+        | R_LED_BEHAVIOUR_2
+        | R_LED_PULSE_1_PER..=R_LED_BREATHE_PER
+        | R_LED_CONFIG
+        | R_LED_PULSE_1_DUT..=R_LED_OFF_DELAY
+    )
+}
+
+#[derive(Debug, Clone)]
+pub(crate) struct RegisterCache {
+    values: [u8; CACHE_LEN],
+    valid: u128,
+}
+
+impl RegisterCache {
+    pub fn new() -> Self {
+        Self {
+            values: [0; CACHE_LEN],
+            valid: 0,
+        }
+    }
+
+    pub fn get(&self, register: u8) -> Option<u8> {
+        if is_cacheable(register) {
+            let index = register - CACHE_START;
+            if self.valid & (1 << index) != 0 {
+                return Some(self.values[index as usize]);
+            }
+        }
+        None
+    }
+
+    pub fn set(&mut self, register: u8, value: u8) {
+        if is_cacheable(register) {
+            let index = register - CACHE_START;
+            self.values[index as usize] = value;
+            self.valid |= 1 << index;
+        }
+    }
+
+    /// Record consecutive register values, skipping the ones that cannot be cached
+    pub fn set_block(&mut self, register: u8, values: &[u8]) {
+        for (offset, value) in values.iter().enumerate() {
+            if let Some(register) = register.checked_add(offset as u8) {
+                self.set(register, *value);
+            }
+        }
+    }
+
+    pub fn invalidate(&mut self) {
+        self.valid = 0;
+    }
+}
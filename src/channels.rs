@@ -0,0 +1,33 @@
+use core::ops::{Deref, DerefMut};
+
+/// One value per sensor input, without allocation
+///
+/// Dereferences to a slice of as many values as the device has inputs, at most 8.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ChannelValues<V> {
+    values: [V; 8],
+    len: u8,
+}
+
+impl<V: Copy + Default> ChannelValues<V> {
+    pub(crate) fn new(len: u8) -> Self {
+        Self {
+            values: [V::default(); 8],
+            len: len.min(8),
+        }
+    }
+}
+
+impl<V> Deref for ChannelValues<V> {
+    type Target = [V];
+
+    fn deref(&self) -> &[V] {
+        &self.values[..self.len as usize]
+    }
+}
+
+impl<V> DerefMut for ChannelValues<V> {
+    fn deref_mut(&mut self) -> &mut [V] {
+        &mut self.values[..self.len as usize]
+    }
+}
@@ -0,0 +1,193 @@
+//! embedded-hal 0.2 compatibility
+//!
+//! [`I2cCompat`] wraps an embedded-hal 0.2 bus implementing the blocking `Write` and
+//! `WriteRead` traits so it can be used with [`CAP1XXX`](crate::CAP1XXX):
+//!
+//! ```ignore
+//! let cap = CAP1XXX::detect(I2cCompat::new(i2c), 0x2C)?;
+//! ```
+use core::fmt::{self, Debug, Display};
+use eh02::blocking::i2c::{Write, WriteRead};
+use embedded_hal::i2c::{ErrorKind, ErrorType, I2c, Operation};
+
+/// Longest write transaction supported by the adapter
+const MAX_WRITE_LEN: usize = 32;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CompatError<R, W> {
+    /// Error of the `WriteRead` implementation
+    Read(R),
+    /// Error of the `Write` implementation
+    Write(W),
+    /// The transaction cannot be expressed with the embedded-hal 0.2 traits: only
+    /// writes optionally followed by a single read are supported.
+    Unsupported,
+}
+
+impl<R: Display, W: Display> Display for CompatError<R, W> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            CompatError::Read(e) => write!(f, "I2C ReadError: {}", e),
+            CompatError::Write(e) => write!(f, "I2C WriteError: {}", e),
+            CompatError::Unsupported => write!(f, "Unsupported I2C transaction"),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl<R, W> std::error::Error for CompatError<R, W>
+where
+    R: std::error::Error,
+    W: std::error::Error,
+{
+}
+
+impl<R: Debug, W: Debug> embedded_hal::i2c::Error for CompatError<R, W> {
+    fn kind(&self) -> ErrorKind {
+        ErrorKind::Other
+    }
+}
+
+/// embedded-hal 1.0 `I2c` implementation over an embedded-hal 0.2 bus
+#[derive(Debug, Clone)]
+pub struct I2cCompat<T>(T);
+
+impl<T> I2cCompat<T> {
+    pub fn new(i2c: T) -> Self {
+        I2cCompat(i2c)
+    }
+
+    pub fn into_inner(self) -> T {
+        self.0
+    }
+}
+
+impl<T> ErrorType for I2cCompat<T>
+where
+    T: WriteRead + Write,
+    <T as WriteRead>::Error: Debug,
+    <T as Write>::Error: Debug,
+{
+    type Error = CompatError<<T as WriteRead>::Error, <T as Write>::Error>;
+}
+
+impl<T> I2c for I2cCompat<T>
+where
+    T: WriteRead + Write,
+    <T as WriteRead>::Error: Debug,
+    <T as Write>::Error: Debug,
+{
+    fn read(&mut self, _address: u8, _read: &mut [u8]) -> Result<(), Self::Error> {
+        Err(CompatError::Unsupported)
+    }
+
+    fn write(&mut self, address: u8, write: &[u8]) -> Result<(), Self::Error> {
+        self.0.write(address, write).map_err(CompatError::Write)
+    }
+
+    fn write_read(
+        &mut self,
+        address: u8,
+        write: &[u8],
+        read: &mut [u8],
+    ) -> Result<(), Self::Error> {
+        self.0
+            .write_read(address, write, read)
+            .map_err(CompatError::Read)
+    }
+
+    /// Consecutive writes are merged into a single write
+    fn transaction(
+        &mut self,
+        address: u8,
+        operations: &mut [Operation<'_>],
+    ) -> Result<(), Self::Error> {
+        let mut buffer = [0u8; MAX_WRITE_LEN];
+        let mut len = 0;
+        let count = operations.len();
+        for (index, operation) in operations.iter_mut().enumerate() {
+            match operation {
+                Operation::Write(bytes) => {
+                    let end = len + bytes.len();
+                    if end > MAX_WRITE_LEN {
+                        return Err(CompatError::Unsupported);
+                    }
+                    buffer[len..end].copy_from_slice(bytes);
+                    len = end;
+                }
+                Operation::Read(read) if index == count - 1 && len > 0 => {
+                    return self.write_read(address, &buffer[..len], read);
+                }
+                Operation::Read(_) => return Err(CompatError::Unsupported),
+            }
+        }
+        self.write(address, &buffer[..len])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mock::Emulator;
+    use crate::CAP1XXX;
+
+    // embedded-hal 0.2 bus, forwarding to the emulator
+    struct Legacy(Emulator);
+
+    impl Write for Legacy {
+        type Error = crate::mock::Error;
+
+        fn write(&mut self, address: u8, bytes: &[u8]) -> Result<(), Self::Error> {
+            I2c::write(&mut self.0, address, bytes)
+        }
+    }
+
+    impl WriteRead for Legacy {
+        type Error = crate::mock::Error;
+
+        fn write_read(
+            &mut self,
+            address: u8,
+            bytes: &[u8],
+            buffer: &mut [u8],
+        ) -> Result<(), Self::Error> {
+            I2c::write_read(&mut self.0, address, bytes, buffer)
+        }
+    }
+
+    #[test]
+    fn driver_over_legacy_bus() {
+        let emulator = Emulator::new();
+        let i2c = I2cCompat::new(Legacy(emulator.clone()));
+        let mut cap = CAP1XXX::detect(i2c, 0x28).unwrap();
+        cap.init().unwrap();
+        cap.set_thresholds(&[1, 2, 3, 4, 5, 6]).unwrap();
+        assert_eq!(cap.read_threshold_values().unwrap()[..], [1, 2, 3, 4, 5, 6]);
+
+        emulator.press(1);
+        assert!(cap.is_interrupted().unwrap());
+        assert_eq!(cap.read_input_status().unwrap(), 0b10);
+    }
+
+    #[test]
+    fn unsupported_transactions() {
+        let mut i2c = I2cCompat::new(Legacy(Emulator::new()));
+        let mut buf = [0u8; 2];
+        assert_eq!(i2c.read(0x28, &mut buf), Err(CompatError::Unsupported));
+        assert_eq!(
+            i2c.transaction(
+                0x28,
+                &mut [Operation::Read(&mut buf), Operation::Write(&[0])]
+            ),
+            Err(CompatError::Unsupported)
+        );
+        assert_eq!(
+            i2c.transaction(0x28, &mut [Operation::Write(&[0; 33])]),
+            Err(CompatError::Unsupported)
+        );
+        assert_eq!(
+            i2c.write(0x2C, &[0]),
+            Err(CompatError::Write(crate::mock::Error::Nack(0x2C)))
+        );
+    }
+}
@@ -0,0 +1,639 @@
+//! Typed values of the configuration registers
+use core::time::Duration;
+
+/// Noise threshold, as a percentage of the touch threshold.
+///
+/// Signals above it but below the touch threshold flag the input as noisy.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum NoiseThreshold {
+    Percent25 = 0b00,
+    /// Power-on default
+    Percent37_5 = 0b01,
+    Percent50 = 0b10,
+    Percent62_5 = 0b11,
+}
+
+impl NoiseThreshold {
+    pub fn from_bits(bits: u8) -> Self {
+        match bits & 0b11 {
+            0b00 => NoiseThreshold::Percent25,
+            0b01 => NoiseThreshold::Percent37_5,
+            0b10 => NoiseThreshold::Percent50,
+            _ => NoiseThreshold::Percent62_5,
+        }
+    }
+
+    pub fn bits(self) -> u8 {
+        self as u8
+    }
+
+    /// Fraction of the touch threshold, from 0.25 to 0.625
+    pub fn fraction(self) -> f32 {
+        0.25 + 0.125 * f32::from(self.bits())
+    }
+}
+
+/// Sensitivity multiplier of the touch detection, higher values detect
+/// smaller capacitance changes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Sensitivity {
+    X128 = 0b000,
+    X64 = 0b001,
+    /// Power-on default
+    X32 = 0b010,
+    X16 = 0b011,
+    X8 = 0b100,
+    X4 = 0b101,
+    X2 = 0b110,
+    X1 = 0b111,
+}
+
+impl Sensitivity {
+    pub fn from_bits(bits: u8) -> Self {
+        match bits & 0b111 {
+            0b000 => Sensitivity::X128,
+            0b001 => Sensitivity::X64,
+            0b010 => Sensitivity::X32,
+            0b011 => Sensitivity::X16,
+            0b100 => Sensitivity::X8,
+            0b101 => Sensitivity::X4,
+            0b110 => Sensitivity::X2,
+            _ => Sensitivity::X1,
+        }
+    }
+
+    pub fn bits(self) -> u8 {
+        self as u8
+    }
+
+    /// Multiplier value, from 1 to 128
+    pub fn multiplier(self) -> u8 {
+        1 << (7 - self.bits())
+    }
+
+    /// Sensitivity of a multiplier value, which must be a power of 2 from 1 to 128
+    pub fn from_multiplier(multiplier: u8) -> Option<Self> {
+        if multiplier.is_power_of_two() {
+            Some(Self::from_bits(7 - multiplier.trailing_zeros() as u8))
+        } else {
+            None
+        }
+    }
+}
+
+/// Scaling applied to the base counts; it does not change touch detection.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum BaseShift {
+    X1 = 0,
+    X2 = 1,
+    X4 = 2,
+    X8 = 3,
+    X16 = 4,
+    X32 = 5,
+    X64 = 6,
+    X128 = 7,
+    /// Power-on default
+    X256 = 8,
+}
+
+impl BaseShift {
+    /// Any value from 8 to 15 means 256x
+    pub fn from_bits(bits: u8) -> Self {
+        match bits & 0b1111 {
+            0 => BaseShift::X1,
+            1 => BaseShift::X2,
+            2 => BaseShift::X4,
+            3 => BaseShift::X8,
+            4 => BaseShift::X16,
+            5 => BaseShift::X32,
+            6 => BaseShift::X64,
+            7 => BaseShift::X128,
+            _ => BaseShift::X256,
+        }
+    }
+
+    pub fn bits(self) -> u8 {
+        self as u8
+    }
+
+    /// Multiplier value, from 1 to 256
+    pub fn multiplier(self) -> u16 {
+        1 << self.bits()
+    }
+}
+
+/// Number of samples taken per measurement
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Averaging {
+    Samples1 = 0b000,
+    Samples2 = 0b001,
+    Samples4 = 0b010,
+    Samples8 = 0b011,
+    Samples16 = 0b100,
+    Samples32 = 0b101,
+    Samples64 = 0b110,
+    Samples128 = 0b111,
+}
+
+impl Averaging {
+    pub fn from_bits(bits: u8) -> Self {
+        match bits & 0b111 {
+            0b000 => Averaging::Samples1,
+            0b001 => Averaging::Samples2,
+            0b010 => Averaging::Samples4,
+            0b011 => Averaging::Samples8,
+            0b100 => Averaging::Samples16,
+            0b101 => Averaging::Samples32,
+            0b110 => Averaging::Samples64,
+            _ => Averaging::Samples128,
+        }
+    }
+
+    pub fn bits(self) -> u8 {
+        self as u8
+    }
+
+    pub fn samples(self) -> u8 {
+        1 << self.bits()
+    }
+}
+
+/// Duration of a single sample
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum SampleTime {
+    Us320 = 0b00,
+    Us640 = 0b01,
+    Ms1_28 = 0b10,
+    Ms2_56 = 0b11,
+}
+
+impl SampleTime {
+    pub fn from_bits(bits: u8) -> Self {
+        match bits & 0b11 {
+            0b00 => SampleTime::Us320,
+            0b01 => SampleTime::Us640,
+            0b10 => SampleTime::Ms1_28,
+            _ => SampleTime::Ms2_56,
+        }
+    }
+
+    pub fn bits(self) -> u8 {
+        self as u8
+    }
+
+    pub fn duration(self) -> Duration {
+        Duration::from_micros(320 << self.bits())
+    }
+}
+
+/// Time between two measurement cycles
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum CycleTime {
+    Ms35 = 0b00,
+    Ms70 = 0b01,
+    Ms105 = 0b10,
+    Ms140 = 0b11,
+}
+
+impl CycleTime {
+    pub fn from_bits(bits: u8) -> Self {
+        match bits & 0b11 {
+            0b00 => CycleTime::Ms35,
+            0b01 => CycleTime::Ms70,
+            0b10 => CycleTime::Ms105,
+            _ => CycleTime::Ms140,
+        }
+    }
+
+    pub fn bits(self) -> u8 {
+        self as u8
+    }
+
+    pub fn duration(self) -> Duration {
+        Duration::from_millis(35 * (u64::from(self.bits()) + 1))
+    }
+}
+
+/// Standby mode configuration
+///
+/// In standby, only the `channels` inputs are sampled, using their own
+/// sampling, sensitivity and threshold settings.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct StandbyConfig {
+    /// Bitmap of the inputs active in standby
+    pub channels: u8,
+    /// Sum the samples instead of averaging them
+    pub summation: bool,
+    pub averaging: Averaging,
+    pub sample_time: SampleTime,
+    pub cycle_time: CycleTime,
+    pub sensitivity: Sensitivity,
+    /// Touch threshold (0 to 127, higher values are clamped) of the standby inputs
+    pub threshold: u8,
+}
+
+/// Power-on standby configuration
+impl Default for StandbyConfig {
+    fn default() -> Self {
+        Self::from_registers([0x00, 0x39, 0x02, 0x40])
+    }
+}
+
+impl StandbyConfig {
+    /// Decode the standby channel, configuration, sensitivity and threshold registers
+    pub fn from_registers(registers: [u8; 4]) -> Self {
+        let [channels, config, sensitivity, threshold] = registers;
+        Self {
+            channels,
+            summation: config & 0x80 != 0,
+            averaging: Averaging::from_bits(config >> 4),
+            sample_time: SampleTime::from_bits(config >> 2),
+            cycle_time: CycleTime::from_bits(config),
+            sensitivity: Sensitivity::from_bits(sensitivity),
+            threshold: threshold & 0x7F,
+        }
+    }
+
+    /// Encode the standby channel, configuration, sensitivity and threshold registers
+    pub fn to_registers(&self) -> [u8; 4] {
+        [
+            self.channels,
+            (self.summation as u8) << 7
+                | self.averaging.bits() << 4
+                | self.sample_time.bits() << 2
+                | self.cycle_time.bits(),
+            self.sensitivity.bits(),
+            self.threshold.min(0x7F),
+        ]
+    }
+}
+
+/// Sampling of the inputs in active mode, trading latency for noise immunity
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct SamplingConfig {
+    pub averaging: Averaging,
+    pub sample_time: SampleTime,
+    pub cycle_time: CycleTime,
+}
+
+/// Power-on sampling configuration: 8 samples, 1.28ms, 70ms
+impl Default for SamplingConfig {
+    fn default() -> Self {
+        Self::from_register(0x39)
+    }
+}
+
+impl SamplingConfig {
+    pub fn from_register(value: u8) -> Self {
+        Self {
+            averaging: Averaging::from_bits(value >> 4),
+            sample_time: SampleTime::from_bits(value >> 2),
+            cycle_time: CycleTime::from_bits(value),
+        }
+    }
+
+    pub fn to_register(&self) -> u8 {
+        self.averaging.bits() << 4 | self.sample_time.bits() << 2 | self.cycle_time.bits()
+    }
+}
+
+/// Number of simultaneous touches reported before multiple touch blocking kicks in
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum TouchLimit {
+    /// Power-on default
+    Touches1 = 0b00,
+    Touches2 = 0b01,
+    Touches3 = 0b10,
+    Touches4 = 0b11,
+}
+
+impl TouchLimit {
+    pub fn from_bits(bits: u8) -> Self {
+        match bits & 0b11 {
+            0b00 => TouchLimit::Touches1,
+            0b01 => TouchLimit::Touches2,
+            0b10 => TouchLimit::Touches3,
+            _ => TouchLimit::Touches4,
+        }
+    }
+
+    pub fn bits(self) -> u8 {
+        self as u8
+    }
+
+    /// Limit of a number of touches, clamped from 1 to 4
+    pub fn from_count(count: u8) -> Self {
+        Self::from_bits(count.clamp(1, 4) - 1)
+    }
+
+    /// Number of touches, from 1 to 4
+    pub fn count(self) -> u8 {
+        self.bits() + 1
+    }
+}
+
+/// Threshold of the multiple-touch pattern detector, as a percentage of the touch
+/// threshold of each input.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum PatternThreshold {
+    /// Power-on default
+    Percent12_5 = 0b00,
+    Percent25 = 0b01,
+    Percent37_5 = 0b10,
+    Percent100 = 0b11,
+}
+
+impl PatternThreshold {
+    pub fn from_bits(bits: u8) -> Self {
+        match bits & 0b11 {
+            0b00 => PatternThreshold::Percent12_5,
+            0b01 => PatternThreshold::Percent25,
+            0b10 => PatternThreshold::Percent37_5,
+            _ => PatternThreshold::Percent100,
+        }
+    }
+
+    pub fn bits(self) -> u8 {
+        self as u8
+    }
+
+    /// Fraction of the touch threshold: 0.125, 0.25, 0.375 or 1
+    pub fn fraction(self) -> f32 {
+        match self {
+            PatternThreshold::Percent100 => 1.0,
+            _ => 0.125 * f32::from(self.bits() + 1),
+        }
+    }
+}
+
+/// How the inputs of a multiple-touch pattern are matched
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum PatternMode {
+    /// At least as many inputs as there are in the pattern, whichever they are, are
+    /// above the threshold; power-on default
+    Count,
+    /// All the inputs of the pattern are above the threshold
+    Pattern,
+}
+
+/// Multiple-touch pattern (MTP) detection
+///
+/// The detector flags touches matching a pattern, such as a palm covering the panel, so
+/// that they can be handled as noise rather than as separate presses.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct MultipleTouchPattern {
+    pub enabled: bool,
+    pub mode: PatternMode,
+    pub threshold: PatternThreshold,
+    /// Assert the interrupt when the pattern is detected
+    pub alert: bool,
+    /// Bitmap of the inputs of the pattern
+    pub inputs: u8,
+}
+
+impl MultipleTouchPattern {
+    /// Decode the pattern configuration and pattern registers
+    pub fn from_registers(registers: [u8; 2]) -> Self {
+        let [config, inputs] = registers;
+        Self {
+            enabled: config & 0x80 != 0,
+            mode: if config & 0b10 != 0 {
+                PatternMode::Pattern
+            } else {
+                PatternMode::Count
+            },
+            threshold: PatternThreshold::from_bits(config >> 2),
+            alert: config & 1 != 0,
+            inputs,
+        }
+    }
+
+    /// Encode the pattern configuration and pattern registers
+    pub fn to_registers(&self) -> [u8; 2] {
+        [
+            (self.enabled as u8) << 7
+                | self.threshold.bits() << 2
+                | ((self.mode == PatternMode::Pattern) as u8) << 1
+                | self.alert as u8,
+            self.inputs,
+        ]
+    }
+}
+
+/// Behaviour of a LED when driven by the host or linked to its input
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum LedBehaviour {
+    /// Power-on default
+    Direct = 0b00,
+    Pulse1 = 0b01,
+    Pulse2 = 0b10,
+    Breathe = 0b11,
+}
+
+impl LedBehaviour {
+    pub fn from_bits(bits: u8) -> Self {
+        match bits & 0b11 {
+            0b00 => LedBehaviour::Direct,
+            0b01 => LedBehaviour::Pulse1,
+            0b10 => LedBehaviour::Pulse2,
+            _ => LedBehaviour::Breathe,
+        }
+    }
+
+    pub fn bits(self) -> u8 {
+        self as u8
+    }
+}
+
+/// Minimum and maximum duty cycles (0 to 15) of a LED behaviour
+///
+/// The duty cycle steps are not linear, see the datasheet.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct LedDuty {
+    pub min: u8,
+    pub max: u8,
+}
+
+impl LedDuty {
+    pub fn from_register(value: u8) -> Self {
+        Self {
+            min: value & 0x0F,
+            max: value >> 4,
+        }
+    }
+
+    pub fn to_register(&self) -> u8 {
+        self.max << 4 | (self.min & 0x0F)
+    }
+}
+
+/// Duty cycles of all the LED behaviours, written in a single burst
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(default))]
+pub struct LedDuties {
+    pub pulse1: LedDuty,
+    pub pulse2: LedDuty,
+    pub breathe: LedDuty,
+    pub direct: LedDuty,
+}
+
+/// Power-on duty cycles: 0% to 100% for all behaviours
+impl Default for LedDuties {
+    fn default() -> Self {
+        Self::from_registers([0xF0; 4])
+    }
+}
+
+impl LedDuties {
+    /// Decode the pulse 1, pulse 2, breathe and direct duty cycle registers
+    pub fn from_registers(registers: [u8; 4]) -> Self {
+        let [pulse1, pulse2, breathe, direct] = registers;
+        Self {
+            pulse1: LedDuty::from_register(pulse1),
+            pulse2: LedDuty::from_register(pulse2),
+            breathe: LedDuty::from_register(breathe),
+            direct: LedDuty::from_register(direct),
+        }
+    }
+
+    /// Encode the pulse 1, pulse 2, breathe and direct duty cycle registers
+    pub fn to_registers(&self) -> [u8; 4] {
+        [
+            self.pulse1.to_register(),
+            self.pulse2.to_register(),
+            self.breathe.to_register(),
+            self.direct.to_register(),
+        ]
+    }
+}
+
+/// Device configuration applied by [`CAP1XXX::init_with`](crate::CAP1XXX::init_with)
+///
+/// The default configuration is the one applied by [`CAP1XXX::init`](crate::CAP1XXX::init):
+/// all inputs enabled with interrupts, repeat disabled, multi-touch allowed, 210ms hold
+/// delay and repeat rate, 1 sample per measure at 1.28ms every 35ms, 2x sensitivity and
+/// recalibration of the first 6 inputs.
+///
+/// ```
+/// use cap1xxx::config::{Config, Sensitivity};
+///
+/// let config = Config::default()
+///     .inputs(0b0011_1111)
+///     .repeat(0b0000_0011)
+///     .sensitivity(Sensitivity::X8);
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Config {
+    pub(crate) inputs: u8,
+    pub(crate) interrupts: u8,
+    pub(crate) repeat: u8,
+    pub(crate) multitouch: bool,
+    pub(crate) hold_delay: Duration,
+    pub(crate) repeat_rate: Duration,
+    pub(crate) sampling: SamplingConfig,
+    pub(crate) sensitivity: Sensitivity,
+    pub(crate) base_shift: BaseShift,
+    pub(crate) calibration: u8,
+    pub(crate) general_config: u8,
+    pub(crate) configuration2: u8,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            inputs: 0b11111111,
+            interrupts: 0b11111111,
+            // Disable repeat for all channels, but give
+            // it sane defaults anyway
+            repeat: 0b00000000,
+            multitouch: true,
+            hold_delay: Duration::from_millis(210),
+            repeat_rate: Duration::from_millis(210),
+            // Tested sane defaults for various configurations
+            sampling: SamplingConfig {
+                averaging: Averaging::Samples1,
+                sample_time: SampleTime::Ms1_28,
+                cycle_time: CycleTime::Ms35,
+            },
+            sensitivity: Sensitivity::X2,
+            base_shift: BaseShift::X1,
+            calibration: 0b00111111,
+            general_config: 0b00111000,
+            configuration2: 0b01100000,
+        }
+    }
+}
+
+impl Config {
+    /// Bitmap of the enabled inputs
+    pub fn inputs(mut self, inputs: u8) -> Self {
+        self.inputs = inputs;
+        self
+    }
+    /// Bitmap of the inputs raising interrupts
+    pub fn interrupts(mut self, inputs: u8) -> Self {
+        self.interrupts = inputs;
+        self
+    }
+    /// Bitmap of the inputs repeating interrupts when held
+    pub fn repeat(mut self, inputs: u8) -> Self {
+        self.repeat = inputs;
+        self
+    }
+    pub fn multitouch(mut self, enable: bool) -> Self {
+        self.multitouch = enable;
+        self
+    }
+    /// Time before a press and hold is detected, from 35 to 560ms
+    pub fn hold_delay(mut self, delay: Duration) -> Self {
+        self.hold_delay = delay;
+        self
+    }
+    /// Repeat rate of held inputs, from 35 to 560ms
+    pub fn repeat_rate(mut self, rate: Duration) -> Self {
+        self.repeat_rate = rate;
+        self
+    }
+    pub fn sampling(mut self, sampling: SamplingConfig) -> Self {
+        self.sampling = sampling;
+        self
+    }
+    pub fn sensitivity(mut self, sensitivity: Sensitivity) -> Self {
+        self.sensitivity = sensitivity;
+        self
+    }
+    pub fn base_shift(mut self, base_shift: BaseShift) -> Self {
+        self.base_shift = base_shift;
+        self
+    }
+    /// Bitmap of the inputs to recalibrate
+    pub fn calibration(mut self, inputs: u8) -> Self {
+        self.calibration = inputs;
+        self
+    }
+    /// Raw value of the general configuration register
+    pub fn general_config(mut self, value: u8) -> Self {
+        self.general_config = value;
+        self
+    }
+    /// Raw value of the configuration 2 register
+    pub fn configuration2(mut self, value: u8) -> Self {
+        self.configuration2 = value;
+        self
+    }
+}
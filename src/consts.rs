@@ -6,10 +6,25 @@ pub const PID_CAP1208: u8 = 0b01101011;
 pub const PID_CAP1188: u8 = 0b01010000;
 pub const PID_CAP1166: u8 = 0b01010001;
 
+pub const MANUFACTURER_ID_MICROCHIP: u8 = 0x5D;
+
 // REGISTER MAP
 
 pub const R_MAIN_CONTROL: u8 = 0x00;
+// B7..B6 = Gain
+// B5 = Standby ( 1 = Only standby inputs are sampled )
+// B4 = Deep Sleep
+// B3..B1 = N/A
+// B0 = Interrupt ( Cleared by writing 0 )
 pub const R_GENERAL_STATUS: u8 = 0x02;
+// B7 = N/A
+// B6 = BC_OUT ( Base count out of limit )
+// B5 = ACAL_FAIL ( Analog calibration failure )
+// B4 = PWR ( Power button, cap1208 only )
+// B3 = LED ( LED ramping done, cap1166 and cap1188 )
+// B2 = MULT ( Multiple touches blocked )
+// B1 = MTP ( Multiple touch pattern detected )
+// B0 = TOUCH ( Any input touched )
 pub const R_INPUT_STATUS: u8 = 0x03;
 pub const R_LED_STATUS: u8 = 0x04;
 pub const R_NOISE_FLAG_STATUS: u8 = 0x0A;
@@ -25,14 +40,14 @@ pub const R_INPUT_7_DELTA: u8 = 0x16;
 pub const R_INPUT_8_DELTA: u8 = 0x17;
 
 pub const R_SENSITIVITY: u8 = 0x1F;
+// Default 0b00101111
 // B7     = N/A
-// B6..B4 = Sensitivity
-// B3..B0 = Base Shift
-//const SENSITIVITY = {128: 0b000, 64:0b001, 32:0b010, 16:0b011, 8:0b100, 4:0b100, 2:0b110, 1:0b111}
+// B6..B4 = Sensitivity ( see config::Sensitivity, 0b000 = 128x to 0b111 = 1x )
+// B3..B0 = Base Shift ( see config::BaseShift, 0b0000 = 1x to 0b1000 and above = 256x )
 
 pub const R_GENERAL_CONFIG: u8 = 0x20;
 // B7 = Timeout
-// B6 = Wake Config ( 1 = Wake pin asserted )
+// B6 = Wake Config ( 1 = Wake pin asserted on touch in standby )
 // B5 = Disable Digital Noise ( 1 = Noise threshold disabled )
 // B4 = Disable Analog Noise ( 1 = Low frequency analog noise blocking disabled )
 // B3 = Max Duration Recalibration ( 1 =  Enable recalibration if touch is held longer than max duration )
@@ -53,7 +68,13 @@ pub const R_INPUT_CONFIG2: u8 = 0x23; //  # Default 0x00000111
 //
 // Resolution of 35ms, max = 35 + (35 * 0b1111) = 560ms
 
-pub const R_SAMPLING_CONFIG: u8 = 0x24; //  # Default 0x00111001
+pub const R_SAMPLING_CONFIG: u8 = 0x24;
+// Default 0b00111001
+// B7 = N/A
+// B6..B4 = Samples per measure ( 1 to 128 )
+// B3..B2 = Sample time ( 320us, 640us, 1.28ms, 2.56ms )
+// B1..B0 = Cycle time ( 35ms, 70ms, 105ms, 140ms )
+
 pub const R_CALIBRATION: u8 = 0x26; //  # Default 0b00000000
 pub const R_INTERRUPT_EN: u8 = 0x27; //  # Default 0b11111111
 pub const R_REPEAT_EN: u8 = 0x28; //  # Default 0b11111111
@@ -62,6 +83,12 @@ pub const R_MTOUCH_PAT_CONF: u8 = 0x2B;
 pub const R_MTOUCH_PATTERN: u8 = 0x2D;
 pub const R_COUNT_O_LIMIT: u8 = 0x2E;
 pub const R_RECALIBRATION: u8 = 0x2F;
+// Default 0b10001010
+// B7 = Button Load Threshold ( 1 = Writing input 1 threshold updates all thresholds )
+// B6 = No Clear Intermediate Data
+// B5 = No Clear Negative Delta Count
+// B4..B3 = Negative Delta Count
+// B2..B0 = Calibration Config
 
 // R/W Touch detection thresholds for inputs
 pub const R_INPUT_1_THRESH: u8 = 0x30;
@@ -75,12 +102,21 @@ pub const R_INPUT_8_THRESH: u8 = 0x37;
 
 // R/W Noise threshold for all inputs
 pub const R_NOISE_THRESH: u8 = 0x38;
+// B7..B2 = N/A
+// B1..B0 = Noise threshold ( 25%, 37.5%, 50%, 62.5% of touch threshold )
 
 // R/W Standby and Config Registers
 pub const R_STANDBY_CHANNEL: u8 = 0x40;
 pub const R_STANDBY_CONFIG: u8 = 0x41;
 pub const R_STANDBY_SENS: u8 = 0x42;
 pub const R_STANDBY_THRESH: u8 = 0x43;
+// R_STANDBY_CONFIG:
+// B7 = Summation ( 1 = Samples are summed, 0 = averaged )
+// B6..B4 = Samples per measure ( 1 to 128 )
+// B3..B2 = Sample time ( 320us, 640us, 1.28ms, 2.56ms )
+// B1..B0 = Cycle time ( 35ms, 70ms, 105ms, 140ms )
+// R_STANDBY_SENS:
+// B2..B0 = Sensitivity, same values as R_SENSITIVITY B6..B4
 
 pub const R_CONFIGURATION2: u8 = 0x44;
 // B7 = Linked LED Transition Controls ( 1 = LED trigger is !touch )
@@ -1,101 +1,68 @@
-use std::fmt;
-use std::fmt::{Debug, Display};
+use core::fmt;
+use core::fmt::{Debug, Display};
+use embedded_hal::digital::ErrorKind;
 
-// Read Error
-pub struct ReadError<T>(T);
-
-//impl<R: Read> !Read for ReadError<R>;
-
-impl<T> From<T> for ReadError<T> {
-    fn from(read_error: T) -> Self {
-        ReadError(read_error)
-    }
-}
-
-impl<T: Display> Display for ReadError<T> {
-    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        write!(f, "I2C ReadError: {}", self.0)
-    }
-}
-
-impl<T: Debug> Debug for ReadError<T> {
-    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        write!(f, "ReadError {:?}", self.0)
-    }
-}
-
-impl<T: std::error::Error> std::error::Error for ReadError<T> {}
-
-// Write Error
-pub struct WriteError<W>(W);
-
-impl<W> From<W> for WriteError<W> {
-    fn from(write_error: W) -> Self {
-        WriteError(write_error)
-    }
-}
-
-impl<T: Display> Display for WriteError<T> {
-    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        write!(f, "I2C WriteError: {}", self.0)
-    }
-}
-impl<T: Debug> Debug for WriteError<T> {
-    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        write!(f, "WriteError {:?}", self.0)
-    }
-}
-
-impl<T: std::error::Error> std::error::Error for WriteError<T> {}
-
-// Combined Read or Write Error
-pub enum Error<R, W> {
-    ReadError(ReadError<R>),
-    WriteError(WriteError<W>),
+/// Driver error, `E` being the error of the I2C bus
+pub enum Error<E> {
+    I2cError(E),
     LedNumberOverflowError,
+    ChannelNumberOverflowError,
+    /// The MANUFACTURER_ID register does not identify a Microchip device
+    UnknownManufacturerError(u8),
+    /// The PRODUCT_ID register does not match any supported chip
+    UnsupportedProductError(u8),
+    /// Reading the level of the ALERT pin failed
+    AlertPinError(ErrorKind),
+    /// The calibration did not complete in time
+    CalibrationTimeout,
+    /// A LED pulse count is not within 1 to 8
+    PulseCountError(u8),
 }
 
-impl<R, W> std::error::Error for Error<R, W>
-where
-    R: std::error::Error,
-    W: std::error::Error,
-{
-}
+#[cfg(feature = "std")]
+impl<E> std::error::Error for Error<E> where E: std::error::Error {}
 
-impl<R, W> Display for Error<R, W>
+impl<E> Display for Error<E>
 where
-    R: Display,
-    W: Display,
+    E: Display,
 {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         match &self {
-            Error::ReadError(e) => std::fmt::Display::fmt(&e, f),
-            Error::WriteError(e) => std::fmt::Display::fmt(&e, f),
+            Error::I2cError(e) => write!(f, "I2C Error: {}", e),
             Error::LedNumberOverflowError => write!(f, "Invalid led number!"),
+            Error::ChannelNumberOverflowError => write!(f, "Invalid channel number!"),
+            Error::UnknownManufacturerError(id) => {
+                write!(f, "Unknown manufacturer id: {:#04x}", id)
+            }
+            Error::UnsupportedProductError(id) => write!(f, "Unsupported product id: {:#04x}", id),
+            Error::AlertPinError(kind) => write!(f, "ALERT pin error: {}", kind),
+            Error::CalibrationTimeout => write!(f, "Calibration timeout"),
+            Error::PulseCountError(count) => write!(f, "Invalid pulse count: {}", count),
         }
     }
 }
-impl<R, W> Debug for Error<R, W>
+impl<E> Debug for Error<E>
 where
-    R: Debug,
-    W: Debug,
+    E: Debug,
 {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         match &self {
-            Error::ReadError(e) => std::fmt::Debug::fmt(&e, f),
-            Error::WriteError(e) => std::fmt::Debug::fmt(&e, f),
+            Error::I2cError(e) => write!(f, "I2cError {:?}", e),
             Error::LedNumberOverflowError => write!(f, "LedNumberOverflowError"),
+            Error::ChannelNumberOverflowError => write!(f, "ChannelNumberOverflowError"),
+            Error::UnknownManufacturerError(id) => {
+                write!(f, "UnknownManufacturerError({:#04x})", id)
+            }
+            Error::UnsupportedProductError(id) => write!(f, "UnsupportedProductError({:#04x})", id),
+            Error::AlertPinError(kind) => write!(f, "AlertPinError({:?})", kind),
+            Error::CalibrationTimeout => write!(f, "CalibrationTimeout"),
+            Error::PulseCountError(count) => write!(f, "PulseCountError({})", count),
         }
     }
 }
 
-impl<R, W> From<ReadError<R>> for Error<R, W> {
-    fn from(e: ReadError<R>) -> Self {
-        Error::ReadError(e)
-    }
-}
-impl<R, W> From<WriteError<W>> for Error<R, W> {
-    fn from(e: WriteError<W>) -> Self {
-        Error::WriteError(e)
+impl<E> From<E> for Error<E> {
+    fn from(e: E) -> Self {
+        Error::I2cError(e)
     }
 }
@@ -0,0 +1,273 @@
+use crate::{RWResult, CAP1XXX};
+use core::time::Duration;
+use embedded_hal::i2c::I2c;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TouchEventKind {
+    /// The input started being touched
+    Press,
+    /// The input has been touched for longer than the hold delay
+    Hold,
+    /// The input is still held, emitted every repeat period after the hold
+    Repeat,
+    /// The input is no longer touched
+    Release,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TouchEvent {
+    pub channel: u8,
+    pub kind: TouchEventKind,
+}
+
+/// Events decoded by a single [`TouchDecoder::poll`]
+///
+/// Each channel can at most be pressed then released during one poll.
+#[derive(Debug, Clone)]
+pub struct TouchEvents {
+    events: [TouchEvent; 16],
+    len: usize,
+    pos: usize,
+}
+
+impl TouchEvents {
+    fn new() -> Self {
+        Self {
+            events: [TouchEvent {
+                channel: 0,
+                kind: TouchEventKind::Press,
+            }; 16],
+            len: 0,
+            pos: 0,
+        }
+    }
+
+    fn push(&mut self, channel: u8, kind: TouchEventKind) {
+        self.events[self.len] = TouchEvent { channel, kind };
+        self.len += 1;
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.pos == self.len
+    }
+
+    pub fn as_slice(&self) -> &[TouchEvent] {
+        &self.events[self.pos..self.len]
+    }
+}
+
+impl Iterator for TouchEvents {
+    type Item = TouchEvent;
+
+    fn next(&mut self) -> Option<TouchEvent> {
+        if self.pos < self.len {
+            self.pos += 1;
+            Some(self.events[self.pos - 1])
+        } else {
+            None
+        }
+    }
+}
+
+/// Turns raw input status into press / hold / repeat / release events
+///
+/// The decoder keeps track of the inputs touched at the previous poll and takes care
+/// of clearing the interrupt. Time is given by the caller as a monotonic duration
+/// (eg. elapsed since boot), holds and repeats are only emitted when polling.
+#[derive(Debug, Clone)]
+pub struct TouchDecoder {
+    hold_delay: Duration,
+    repeat_rate: Duration,
+    repeat_inputs: u8,
+    held: u8,
+    hold_reported: u8,
+    pressed_at: [Duration; 8],
+    last_event_at: [Duration; 8],
+}
+
+impl TouchDecoder {
+    /// Create a decoder with explicit timings; `repeat_inputs` is the bitmap of
+    /// inputs emitting repeat events.
+    pub fn new(hold_delay: Duration, repeat_rate: Duration, repeat_inputs: u8) -> Self {
+        Self {
+            hold_delay,
+            repeat_rate,
+            repeat_inputs,
+            held: 0,
+            hold_reported: 0,
+            pressed_at: [Duration::from_millis(0); 8],
+            last_event_at: [Duration::from_millis(0); 8],
+        }
+    }
+
+    /// Create a decoder using the hold delay, repeat rate and repeat enable
+    /// settings currently configured on the device.
+    pub fn from_device<T>(cap: &mut CAP1XXX<T>) -> RWResult<T, Self>
+    where
+        T: I2c,
+    {
+        Ok(Self::new(
+            cap.get_hold_delay()?,
+            cap.get_repeat_rate()?,
+            cap.get_repeat_enabled()?,
+        ))
+    }
+
+    /// Read the input status, clear a pending interrupt and decode the resulting events
+    pub fn poll<T>(&mut self, cap: &mut CAP1XXX<T>, now: Duration) -> RWResult<T, TouchEvents>
+    where
+        T: I2c,
+    {
+        let interrupted = cap.is_interrupted()?;
+        let latched = cap.read_input_status()?;
+        let current = if interrupted {
+            cap.clear_interrupt()?;
+            cap.read_input_status()?
+        } else {
+            latched
+        };
+        Ok(self.decode(latched, current, now))
+    }
+
+    /// Decode events from input status values
+    ///
+    /// `latched` is the status read before clearing the interrupt, it includes
+    /// touches that were already released; `current` is the status read after.
+    pub fn decode(&mut self, latched: u8, current: u8, now: Duration) -> TouchEvents {
+        let mut events = TouchEvents::new();
+        for channel in 0..8 {
+            let mask = 1 << channel;
+            let index = channel as usize;
+            let was_held = self.held & mask != 0;
+            // A touch starting between the two status reads only shows up in `current`
+            if (latched | current) & mask != 0 && !was_held {
+                events.push(channel, TouchEventKind::Press);
+                self.pressed_at[index] = now;
+                self.last_event_at[index] = now;
+                self.hold_reported &= !mask;
+            }
+            if current & mask == 0 {
+                if was_held || latched & mask != 0 {
+                    events.push(channel, TouchEventKind::Release);
+                }
+            } else if self.hold_reported & mask == 0 {
+                if now >= self.pressed_at[index] + self.hold_delay {
+                    events.push(channel, TouchEventKind::Hold);
+                    self.hold_reported |= mask;
+                    self.last_event_at[index] = now;
+                }
+            } else if self.repeat_inputs & mask != 0
+                && now >= self.last_event_at[index] + self.repeat_rate
+            {
+                events.push(channel, TouchEventKind::Repeat);
+                self.last_event_at[index] = now;
+            }
+        }
+        self.held = current;
+        events
+    }
+
+    /// Bitmap of the inputs touched at the last poll
+    pub fn held(&self) -> u8 {
+        self.held
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mock::Emulator;
+    use std::vec;
+    use std::vec::Vec;
+    use TouchEventKind::*;
+
+    fn ms(ms: u64) -> Duration {
+        Duration::from_millis(ms)
+    }
+
+    fn event(channel: u8, kind: TouchEventKind) -> TouchEvent {
+        TouchEvent { channel, kind }
+    }
+
+    fn device() -> (CAP1XXX<Emulator>, Emulator) {
+        let emulator = Emulator::new();
+        let mut cap = CAP1XXX::new(emulator.clone(), 0x28, 6);
+        cap.init().unwrap();
+        (cap, emulator)
+    }
+
+    #[test]
+    fn decoder_uses_device_timings() {
+        let (mut cap, _) = device();
+        cap.set_hold_delay(ms(350)).unwrap();
+        cap.set_repeat_rate(ms(105)).unwrap();
+        cap.enable_repeat(0b100).unwrap();
+        let decoder = TouchDecoder::from_device(&mut cap).unwrap();
+        assert_eq!(decoder.hold_delay, ms(350));
+        assert_eq!(decoder.repeat_rate, ms(105));
+        assert_eq!(decoder.repeat_inputs, 0b100);
+    }
+
+    #[test]
+    fn press_hold_repeat_release() {
+        let (mut cap, emulator) = device();
+        cap.enable_repeat(0b1).unwrap();
+        let mut decoder = TouchDecoder::from_device(&mut cap).unwrap();
+
+        assert!(decoder.poll(&mut cap, ms(0)).unwrap().is_empty());
+
+        emulator.press(0);
+        let events = decoder.poll(&mut cap, ms(10)).unwrap();
+        assert_eq!(events.as_slice(), &[event(0, Press)]);
+        assert!(!cap.is_interrupted().unwrap());
+
+        assert!(decoder.poll(&mut cap, ms(100)).unwrap().is_empty());
+        let events = decoder.poll(&mut cap, ms(220)).unwrap();
+        assert_eq!(events.as_slice(), &[event(0, Hold)]);
+        assert!(decoder.poll(&mut cap, ms(300)).unwrap().is_empty());
+        let events = decoder.poll(&mut cap, ms(430)).unwrap();
+        assert_eq!(events.as_slice(), &[event(0, Repeat)]);
+
+        emulator.release(0);
+        let events = decoder.poll(&mut cap, ms(500)).unwrap();
+        assert_eq!(events.as_slice(), &[event(0, Release)]);
+        assert_eq!(decoder.held(), 0);
+    }
+
+    #[test]
+    fn no_repeat_when_disabled() {
+        let (mut cap, emulator) = device();
+        let mut decoder = TouchDecoder::from_device(&mut cap).unwrap();
+        emulator.press(3);
+        decoder.poll(&mut cap, ms(0)).unwrap();
+        let events = decoder.poll(&mut cap, ms(300)).unwrap();
+        assert_eq!(events.as_slice(), &[event(3, Hold)]);
+        assert!(decoder.poll(&mut cap, ms(1000)).unwrap().is_empty());
+    }
+
+    #[test]
+    fn tap_between_polls() {
+        let (mut cap, emulator) = device();
+        let mut decoder = TouchDecoder::from_device(&mut cap).unwrap();
+        emulator.press(1);
+        emulator.press(4);
+        emulator.release(1);
+        let events: Vec<_> = decoder.poll(&mut cap, ms(0)).unwrap().collect();
+        assert_eq!(
+            events,
+            vec![event(1, Press), event(1, Release), event(4, Press)]
+        );
+        assert_eq!(decoder.held(), 0b10000);
+    }
+
+    #[test]
+    fn touch_starting_after_latched_read() {
+        let mut decoder = TouchDecoder::new(ms(200), ms(100), 0b1);
+        let events: Vec<_> = decoder.decode(0, 0b1, ms(1000)).collect();
+        assert_eq!(events, vec![event(0, Press)]);
+        assert_eq!(decoder.held(), 0b1);
+        assert!(decoder.decode(0b1, 0b1, ms(1100)).is_empty());
+        let events: Vec<_> = decoder.decode(0b1, 0b1, ms(1200)).collect();
+        assert_eq!(events, vec![event(0, Hold)]);
+    }
+}
@@ -2,134 +2,258 @@
 //!
 //! This crate is primarily targeting cap1666 6 buttons - 6 led hardware found
 //! on the Pimoroni GFX HAT
+//!
+//! The driver works on any [`embedded_hal::i2c::I2c`] bus; embedded-hal 0.2 buses can be
+//! used through [`compat::I2cCompat`] with the `eh02` feature.
+//!
+//! An async driver over `embedded-hal-async` is available in [`asynch`] with the `async` feature.
+//!
+//! The crate is `no_std`; the `std` feature adds `std::error::Error` implementations.
+#![no_std]
 #![allow(dead_code)]
-use embedded_hal::blocking::i2c::{Write, WriteRead};
+use embedded_hal::delay::DelayNs;
+use embedded_hal::digital::{Error as _, InputPin};
+use embedded_hal::i2c::{ErrorType, I2c};
 
+#[cfg(any(test, feature = "alloc"))]
+extern crate alloc;
+#[cfg(any(test, feature = "std"))]
+extern crate std;
+
+#[cfg(feature = "async")]
+pub mod asynch;
+mod cache;
+mod channels;
+#[cfg(feature = "eh02")]
+pub mod compat;
+pub mod config;
 mod consts;
 pub mod error;
+mod event;
+#[cfg(feature = "linux")]
+pub mod linux;
+#[cfg(any(test, feature = "mock"))]
+pub mod mock;
+mod model;
+mod power;
+#[cfg(feature = "serde")]
+pub mod profile;
+mod registers;
+mod snapshot;
+mod status;
+use cache::RegisterCache;
+pub use channels::ChannelValues;
+use config::*;
 use consts::*;
+use core::time::Duration;
 use error::*;
-use std::cmp::{max, min};
-use std::time::Duration;
+pub use event::{TouchDecoder, TouchEvent, TouchEventKind, TouchEvents};
+pub use model::{ChipModel, DeviceInfo};
+pub use power::{DeepSleep, PowerState};
+use registers::{Access, CalibrationStep};
+pub use snapshot::RegisterSnapshot;
+pub use status::{GeneralStatus, InputDiagnostics, Status};
 
-pub type RWResult<T, R> = Result<R, Error<<T as WriteRead>::Error, <T as Write>::Error>>;
+pub type RWResult<T, R> = Result<R, Error<<T as ErrorType>::Error>>;
 
 pub struct CAP1XXX<T>
 where
-    T: WriteRead + Write,
+    T: I2c,
 {
     i2c: T,
     number_of_leds: u8,
+    number_of_channels: u8,
     i2c_address: u8,
+    model: Option<ChipModel>,
+    cache: Option<RegisterCache>,
 }
 
+/// Clones do not share the register cache and start with caching disabled, see
+/// [`CAP1XXX::enable_cache`]
 impl<T> Clone for CAP1XXX<T>
 where
-    T: WriteRead + Write + Clone,
+    T: I2c + Clone,
 {
     fn clone(&self) -> Self {
         Self {
             i2c: self.i2c.clone(),
             number_of_leds: self.number_of_leds,
+            number_of_channels: self.number_of_channels,
             i2c_address: self.i2c_address,
+            model: self.model,
+            cache: None,
         }
     }
 }
 
 impl<T> CAP1XXX<T>
 where
-    T: WriteRead + Write,
+    T: I2c,
 {
+    /// Create a driver for a device with as many inputs as LEDs, at most 8
     pub fn new(i2c: T, i2c_address: u8, number_of_leds: u8) -> Self {
+        let number_of_leds = number_of_leds.min(8);
         Self {
             i2c,
             number_of_leds,
+            number_of_channels: number_of_leds,
             i2c_address,
+            model: None,
+            cache: None,
         }
     }
 
-    pub fn init(&mut self) -> RWResult<T, ()> {
-        // Enable all inputs with interrupt by default
-        self.enable_inputs(0b11111111)?;
-        self.enable_interrupts(0b11111111)?;
+    /// Create a driver for the device found at the given address, deriving
+    /// the number of inputs and LEDs from its product id.
+    ///
+    /// Fails if the device is not a supported Microchip part.
+    pub fn detect(i2c: T, i2c_address: u8) -> RWResult<T, Self> {
+        let mut cap = Self::new(i2c, i2c_address, 0);
+        let model = cap.probe()?.model;
+        cap.number_of_leds = model.led_count();
+        cap.number_of_channels = model.channel_count();
+        cap.model = Some(model);
+        Ok(cap)
+    }
 
-        // Disable repeat for all channels, but give
-        // it sane defaults anyway
-        self.enable_repeat(0b00000000)?;
-        self.enable_multitouch(true)?;
+    /// Identify the device by reading its product id, manufacturer id and revision
+    /// in a single burst
+    pub fn probe(&mut self) -> RWResult<T, DeviceInfo> {
+        let mut ids = [0u8; registers::ID_LEN];
+        self.read_block(registers::ID_START, &mut ids)?;
+        registers::device_info(&ids)
+    }
 
-        self.set_hold_delay(Duration::from_millis(210))?;
-        self.set_repeat_rate(Duration::from_millis(210))?;
+    /// Model of the device, if created with [`detect`](Self::detect)
+    pub fn model(&self) -> Option<ChipModel> {
+        self.model
+    }
+    pub fn number_of_leds(&self) -> u8 {
+        self.number_of_leds
+    }
+    pub fn number_of_channels(&self) -> u8 {
+        self.number_of_channels
+    }
 
-        // Tested sane defaults for various configurations
-        self.write_byte(R_SAMPLING_CONFIG, 0b00001000)?; // 1sample per measure, 1.28ms time, 35ms cycle
-        self.write_byte(R_CALIBRATION, 0b00111111)?; // recalibrate
-        self.write_byte(R_SENSITIVITY, 0b01100000)?; // 2x sensitivity
-        self.write_byte(R_GENERAL_CONFIG, 0b00111000)?;
-        self.write_byte(R_CONFIGURATION2, 0b01100000)?;
+    /// Apply the default configuration, see [`Config`]
+    pub fn init(&mut self) -> RWResult<T, ()> {
+        self.init_with(&Config::default())
+    }
 
+    pub fn init_with(&mut self, config: &Config) -> RWResult<T, ()> {
+        for access in registers::init_sequence(config).iter() {
+            self.access(*access)?;
+        }
         Ok(())
     }
 
-    fn write_byte(
-        &mut self,
-        register: u8,
-        value: u8,
-    ) -> Result<(), WriteError<<T as Write>::Error>> {
-        Ok(self.i2c.cmd_write(self.i2c_address, register, &[value])?)
+    fn write_byte(&mut self, register: u8, value: u8) -> RWResult<T, ()> {
+        self.write_block(register, &[value])
+    }
+
+    /// Write consecutive registers in a single transaction
+    fn write_block(&mut self, register: u8, values: &[u8]) -> RWResult<T, ()> {
+        let block = registers::Block::new(register, values);
+        self.i2c.write(self.i2c_address, block.as_bytes())?;
+        if let Some(cache) = &mut self.cache {
+            cache.set_block(register, values);
+        }
+        Ok(())
     }
 
-    fn read_byte(&mut self, register: u8) -> Result<u8, ReadError<<T as WriteRead>::Error>> {
+    /// Served from the register cache when enabled and holding the register
+    fn read_byte(&mut self, register: u8) -> RWResult<T, u8> {
+        if let Some(value) = self.cache.as_ref().and_then(|cache| cache.get(register)) {
+            return Ok(value);
+        }
         let mut buf = [0u8];
-        self.i2c
-            .write_read(self.i2c_address, &[register], &mut buf)?;
+        self.read_block(register, &mut buf)?;
         Ok(buf[0])
     }
 
-    fn read_block(
-        &mut self,
-        register: u8,
-        len: usize,
-    ) -> Result<Vec<u8>, ReadError<<T as WriteRead>::Error>> {
-        let mut buf = vec![0u8; len];
-        self.i2c
-            .write_read(self.i2c_address, &[register], &mut buf)?;
-        Ok(buf)
+    fn read_block(&mut self, register: u8, buf: &mut [u8]) -> RWResult<T, ()> {
+        self.i2c.write_read(self.i2c_address, &[register], buf)?;
+        if let Some(cache) = &mut self.cache {
+            cache.set_block(register, buf);
+        }
+        Ok(())
     }
 
-    fn change_value<F>(&mut self, register: u8, op: F) -> RWResult<T, ()>
-    where
-        F: Fn(u8) -> u8,
-    {
-        let new_value = op(self.read_byte(register)?);
-        Ok(self.write_byte(register, new_value)?)
+    fn access(&mut self, access: Access) -> RWResult<T, ()> {
+        match access {
+            Access::Write { register, value } => self.write_byte(register, value),
+            Access::Modify {
+                register,
+                mask,
+                value,
+            } => {
+                let current = self.read_byte(register)?;
+                self.write_byte(register, Access::modified(mask, value, current))
+            }
+        }
     }
 
-    fn set_bit(&mut self, register: u8, bit: u8) -> RWResult<T, ()> {
-        self.change_value(register, |value| value | (1 << bit))
-    }
+    // ----------------------------------------------------------------------------
+    // Register cache
 
-    fn clear_bit(&mut self, register: u8, bit: u8) -> RWResult<T, ()> {
-        self.change_value(register, |value| value & !(1 << bit))
+    /// Keep a shadow copy of the configuration registers, so that changing some of
+    /// their bits only costs a write.
+    ///
+    /// Registers are cached as they are read or written; use
+    /// [`sync_from_device`](Self::sync_from_device) to load all of them at once. Registers
+    /// updated by the device itself, such as MAIN_CONTROL or the status registers, are
+    /// always read from the device. The cache must be invalidated if the device is reset
+    /// or configured by someone else, including a clone of this driver.
+    pub fn enable_cache(&mut self) {
+        if self.cache.is_none() {
+            self.cache = Some(RegisterCache::new());
+        }
     }
-
-    fn change_bit(&mut self, register: u8, bit: u8, state: bool) -> RWResult<T, ()> {
-        if state {
-            self.set_bit(register, bit)
-        } else {
-            self.clear_bit(register, bit)
+    pub fn disable_cache(&mut self) {
+        self.cache = None;
+    }
+    pub fn is_cache_enabled(&self) -> bool {
+        self.cache.is_some()
+    }
+    /// Forget all cached values, they are read again from the device when needed
+    pub fn invalidate_cache(&mut self) {
+        if let Some(cache) = &mut self.cache {
+            cache.invalidate();
         }
     }
+    /// Load the cache with all the configuration registers in a single burst read,
+    /// enabling the cache if needed.
+    pub fn sync_from_device(&mut self) -> RWResult<T, ()> {
+        let mut cache = RegisterCache::new();
+        let mut values = [0u8; cache::CACHE_LEN];
+        self.i2c
+            .write_read(self.i2c_address, &[cache::CACHE_START], &mut values)?;
+        cache.set_block(cache::CACHE_START, &values);
+        self.cache = Some(cache);
+        Ok(())
+    }
 
-    fn change_bits(&mut self, register: u8, offset: u8, size: u8, bits: u8) -> RWResult<T, ()> {
-        self.change_value(register, |value| {
-            let mut ret = value;
-            for x in 0..size {
-                ret &= !(1 << (offset + x));
-            }
-            ret |= bits << offset;
-            ret
-        })
+    // ----------------------------------------------------------------------------
+    // Register snapshot
+
+    /// Read all the readable registers, one burst per block of consecutive registers
+    pub fn snapshot(&mut self) -> RWResult<T, RegisterSnapshot> {
+        let mut snapshot = RegisterSnapshot::default();
+        for (start, end) in snapshot::READABLE.iter() {
+            self.read_block(*start, snapshot.block_mut(*start, *end))?;
+        }
+        Ok(snapshot)
+    }
+    /// Write back the configuration registers of a snapshot, possibly taken on another device
+    ///
+    /// Only the gain of the main control register is restored, the power state and
+    /// interrupt are left untouched.
+    pub fn restore(&mut self, snapshot: &RegisterSnapshot) -> RWResult<T, ()> {
+        self.access(registers::gain(snapshot.gain_bits()))?;
+        for (start, end) in snapshot::RESTORED.iter() {
+            self.write_block(*start, snapshot.block(*start, *end))?;
+        }
+        Ok(())
     }
 
     // ----------------------------------------------------------------------------
@@ -138,157 +262,395 @@ where
     /// Clear the interrupt flag, bit 0, of the
     //  main control register
     pub fn clear_interrupt(&mut self) -> RWResult<T, ()> {
-        self.clear_bit(R_MAIN_CONTROL, 0)
+        self.access(registers::clear_interrupt())
     }
     pub fn is_interrupted(&mut self) -> RWResult<T, bool> {
-        Ok(self
-            .read_byte(R_MAIN_CONTROL)
-            .map(|value| (value & 1) > 0)?)
+        Ok(registers::interrupted(self.read_byte(R_MAIN_CONTROL)?))
+    }
+    /// Wait until the ALERT pin is asserted, then read the input status and clear
+    /// the interrupt.
+    ///
+    /// The pin is polled every `interval`, honoring the alert polarity configured on the
+    /// device; the async driver waits for the pin without polling. Returns the latched
+    /// input status.
+    pub fn wait_for_touch<P, D>(
+        &mut self,
+        alert: &mut P,
+        interval: Duration,
+        delay: &mut D,
+    ) -> RWResult<T, u8>
+    where
+        P: InputPin,
+        D: DelayNs,
+    {
+        let active_low = self.is_alert_active_low()?;
+        let interval_us = interval.as_micros().min(u128::from(u32::MAX)) as u32;
+        loop {
+            let asserted = if active_low {
+                alert.is_low()
+            } else {
+                alert.is_high()
+            };
+            if asserted.map_err(|e| Error::AlertPinError(e.kind()))? {
+                break;
+            }
+            delay.delay_us(interval_us);
+        }
+        let status = self.read_input_status()?;
+        self.clear_interrupt()?;
+        Ok(status)
+    }
+    /// Active low open drain (the power-on default) or active high push-pull ALERT pin
+    pub fn set_alert_active_low(&mut self, active_low: bool) -> RWResult<T, ()> {
+        self.access(registers::alert_polarity(active_low))
+    }
+    pub fn is_alert_active_low(&mut self) -> RWResult<T, bool> {
+        Ok(registers::alert_active_low(
+            self.read_byte(R_CONFIGURATION2)?,
+        ))
     }
     pub fn auto_recalibrate(&mut self, value: bool) -> RWResult<T, ()> {
-        self.change_bit(R_GENERAL_CONFIG, 3, value)
+        self.access(registers::auto_recalibrate(value))
+    }
+    /// Start the calibration of the inputs of the `channels` bitmap
+    pub fn calibrate(&mut self, channels: u8) -> RWResult<T, ()> {
+        self.access(registers::calibrate(channels, self.number_of_channels))
+    }
+    /// Wait for the calibration of the inputs of the `channels` bitmap to complete
+    ///
+    /// Polls the device every 10ms until it clears their CALIBRATION_ACTIVATE bits or
+    /// raises ACAL_FAIL, and returns the bitmap of the inputs that failed: those still
+    /// calibrating when ACAL_FAIL is raised and, with BC_OUT, those whose base count is
+    /// out of limit. The flags are latched, clear the interrupt before calibrating.
+    ///
+    /// Fails with `Error::CalibrationTimeout` if the calibration still runs after `timeout`.
+    pub fn wait_calibration_done<D: DelayNs>(
+        &mut self,
+        channels: u8,
+        timeout: Duration,
+        delay: &mut D,
+    ) -> RWResult<T, u8> {
+        let mut wait = registers::CalibrationWait::new(channels, timeout);
+        loop {
+            let general = self.read_general_status()?;
+            let pending = self.read_byte(R_CALIBRATION)?;
+            let out_of_limit = if general.base_count_out_of_limit {
+                self.read_byte(R_COUNT_O_LIMIT)?
+            } else {
+                0
+            };
+            match wait.step(&general, pending, out_of_limit)? {
+                CalibrationStep::Done(failed) => return Ok(failed),
+                CalibrationStep::Wait(ms) => delay.delay_ms(ms),
+            }
+        }
     }
     pub fn filter_analog_noise(&mut self, value: bool) -> RWResult<T, ()> {
-        self.change_bit(R_GENERAL_CONFIG, 4, !value)
+        self.access(registers::analog_noise_filter(value))
     }
     pub fn filter_digital_noise(&mut self, value: bool) -> RWResult<T, ()> {
-        self.change_bit(R_GENERAL_CONFIG, 5, !value)
+        self.access(registers::digital_noise_filter(value))
     }
     /// Set time before a press and hold is detected,
     /// Clamps to multiples of 35 from 35 to 560
     pub fn set_hold_delay(&mut self, delay: Duration) -> RWResult<T, ()> {
-        self.change_value(R_INPUT_CONFIG2, |v| {
-            (v & !0b1111) | Self::duration_to_rate_scale(delay)
-        })
+        self.access(registers::hold_delay(delay))
     }
     /// Set repeat rate in milliseconds,
     //  Clamps to multiples of 35 from 35 to 560
     pub fn set_repeat_rate(&mut self, delay: Duration) -> RWResult<T, ()> {
-        self.change_value(R_INPUT_CONFIG, |v| {
-            (v & !0b1111) | Self::duration_to_rate_scale(delay)
-        })
+        self.access(registers::repeat_rate(delay))
+    }
+
+    /// Time before a press and hold is detected
+    pub fn get_hold_delay(&mut self) -> RWResult<T, Duration> {
+        let value = self.read_byte(R_INPUT_CONFIG2)?;
+        Ok(Self::rate_scale_to_duration(value))
+    }
+    /// Repeat rate of held inputs
+    pub fn get_repeat_rate(&mut self) -> RWResult<T, Duration> {
+        let value = self.read_byte(R_INPUT_CONFIG)?;
+        Ok(Self::rate_scale_to_duration(value))
     }
 
     pub fn duration_to_rate_scale(duration: Duration) -> u8 {
-        let ms = duration.as_millis();
-        let ms = max(35, ms);
-        let ms = min(560, ms);
-        ((ms - ms % 35 - 35) / 35) as u8
+        registers::duration_to_rate_scale(duration)
     }
-    fn get_product_id(&mut self) -> RWResult<T, u8> {
-        Ok(self.read_byte(R_PRODUCT_ID)?)
+    pub fn rate_scale_to_duration(scale: u8) -> Duration {
+        registers::rate_scale_to_duration(scale)
+    }
+    pub fn get_product_id(&mut self) -> RWResult<T, u8> {
+        self.read_byte(R_PRODUCT_ID)
+    }
+    pub fn get_manufacturer_id(&mut self) -> RWResult<T, u8> {
+        self.read_byte(R_MANUFACTURER_ID)
+    }
+    pub fn get_revision(&mut self) -> RWResult<T, u8> {
+        self.read_byte(R_REVISION)
     }
 
     /// Toggles multi-touch by toggling the multi-touch block bit in the config register
     pub fn enable_multitouch(&mut self, enable: bool) -> RWResult<T, ()> {
-        self.change_value(R_MTOUCH_CONFIG, |value| {
-            if enable {
-                value & !0x80
-            } else {
-                value | 0x80
-            }
-        })
+        self.access(registers::multitouch(enable))
+    }
+    /// Number of simultaneous touches reported when multi-touch is blocked, see
+    /// [`enable_multitouch`](Self::enable_multitouch); further touches set the MULT flag.
+    pub fn set_touch_limit(&mut self, limit: TouchLimit) -> RWResult<T, ()> {
+        self.access(registers::touch_limit(limit))
+    }
+    pub fn get_touch_limit(&mut self) -> RWResult<T, TouchLimit> {
+        Ok(registers::touch_limit_from(
+            self.read_byte(R_MTOUCH_CONFIG)?,
+        ))
+    }
+    /// MULT flag of the general status: touches are being blocked because more inputs
+    /// than the touch limit are touched
+    pub fn is_multitouch_blocked(&mut self) -> RWResult<T, bool> {
+        Ok(self.read_general_status()?.multitouch_blocked)
+    }
+    /// Configure the multiple-touch pattern detector, see [`MultipleTouchPattern`]
+    pub fn configure_multiple_touch_pattern(
+        &mut self,
+        pattern: &MultipleTouchPattern,
+    ) -> RWResult<T, ()> {
+        for access in registers::multiple_touch_pattern(pattern).iter() {
+            self.access(*access)?;
+        }
+        Ok(())
+    }
+    pub fn get_multiple_touch_pattern(&mut self) -> RWResult<T, MultipleTouchPattern> {
+        Ok(MultipleTouchPattern::from_registers([
+            self.read_byte(R_MTOUCH_PAT_CONF)?,
+            self.read_byte(R_MTOUCH_PATTERN)?,
+        ]))
+    }
+    /// MTP flag of the general status: touches matching the multiple-touch pattern
+    /// were detected. It is latched until the interrupt is cleared.
+    pub fn is_multiple_touch_pattern_detected(&mut self) -> RWResult<T, bool> {
+        Ok(self.read_general_status()?.pattern_detected)
     }
     pub fn enable_repeat(&mut self, inputs: u8) -> RWResult<T, ()> {
-        Ok(self.write_byte(R_REPEAT_EN, inputs)?)
+        self.write_byte(R_REPEAT_EN, inputs)
+    }
+    /// Bitmap of the inputs with repeat enabled
+    pub fn get_repeat_enabled(&mut self) -> RWResult<T, u8> {
+        self.read_byte(R_REPEAT_EN)
     }
     pub fn enable_interrupts(&mut self, inputs: u8) -> RWResult<T, ()> {
-        Ok(self.write_byte(R_INTERRUPT_EN, inputs)?)
+        self.write_byte(R_INTERRUPT_EN, inputs)
+    }
+    /// Bitmap of the inputs with interrupts enabled
+    pub fn get_interrupts_enabled(&mut self) -> RWResult<T, u8> {
+        self.read_byte(R_INTERRUPT_EN)
     }
     pub fn enable_inputs(&mut self, inputs: u8) -> RWResult<T, ()> {
-        Ok(self.write_byte(R_INPUT_ENABLE, inputs)?)
+        self.write_byte(R_INPUT_ENABLE, inputs)
+    }
+    /// Bitmap of the enabled inputs
+    pub fn get_inputs_enabled(&mut self) -> RWResult<T, u8> {
+        self.read_byte(R_INPUT_ENABLE)
     }
 
-    pub fn read_input_status(&mut self) -> Result<u8, ReadError<<T as WriteRead>::Error>> {
+    pub fn read_input_status(&mut self) -> RWResult<T, u8> {
         self.read_byte(R_INPUT_STATUS)
     }
+    pub fn read_general_status(&mut self) -> RWResult<T, GeneralStatus> {
+        Ok(GeneralStatus::from_register(
+            self.read_byte(R_GENERAL_STATUS)?,
+        ))
+    }
+    /// Bitmap of the inputs flagged as noisy, see [`Status::noise`]
+    pub fn read_noise_flags(&mut self) -> RWResult<T, u8> {
+        self.read_byte(R_NOISE_FLAG_STATUS)
+    }
+    /// Read the general, input, LED and noise status in a single burst
+    pub fn read_status(&mut self) -> RWResult<T, Status> {
+        let mut registers = [0u8; status::STATUS_LEN];
+        self.read_block(status::STATUS_START, &mut registers)?;
+        Ok(Status::from_registers(&registers))
+    }
+
+    pub fn read_threshold_values(&mut self) -> RWResult<T, ChannelValues<u8>> {
+        let mut values = ChannelValues::new(self.number_of_channels);
+        self.read_block(R_INPUT_1_THRESH, &mut values)?;
+        Ok(values)
+    }
+    /// Set the touch threshold (0 to 127, higher values are clamped) of one input
+    ///
+    /// If thresholds are linked (the power-on default), setting the threshold of
+    /// the first input sets all of them.
+    pub fn set_threshold(&mut self, channel: u8, threshold: u8) -> RWResult<T, ()> {
+        let access = registers::threshold(channel, threshold, self.number_of_channels)?;
+        self.access(access)
+    }
+    /// Set the touch thresholds (0 to 127, higher values are clamped) of the first inputs
+    /// in a single burst write
+    ///
+    /// If thresholds are linked, the threshold of the first input is also written to
+    /// the inputs missing from `thresholds`: `[1, 2, 3]` sets `[1, 2, 3, 1, 1, 1]`.
+    pub fn set_thresholds(&mut self, thresholds: &[u8]) -> RWResult<T, ()> {
+        let values = registers::thresholds(thresholds, self.number_of_channels)?;
+        self.write_block(R_INPUT_1_THRESH, &values)
+    }
+    /// When linked, writing the first input threshold updates the thresholds of all inputs
+    pub fn link_thresholds(&mut self, linked: bool) -> RWResult<T, ()> {
+        self.access(registers::linked_thresholds(linked))
+    }
+    pub fn set_noise_threshold(&mut self, threshold: NoiseThreshold) -> RWResult<T, ()> {
+        self.access(registers::noise_threshold(threshold))
+    }
+    pub fn get_noise_threshold(&mut self) -> RWResult<T, NoiseThreshold> {
+        Ok(NoiseThreshold::from_bits(self.read_byte(R_NOISE_THRESH)?))
+    }
+
+    pub fn set_sampling_config(&mut self, config: &SamplingConfig) -> RWResult<T, ()> {
+        self.write_byte(R_SAMPLING_CONFIG, config.to_register())
+    }
+    pub fn get_sampling_config(&mut self) -> RWResult<T, SamplingConfig> {
+        Ok(SamplingConfig::from_register(
+            self.read_byte(R_SAMPLING_CONFIG)?,
+        ))
+    }
+
+    pub fn set_sensitivity(&mut self, sensitivity: Sensitivity) -> RWResult<T, ()> {
+        self.access(registers::sensitivity(sensitivity))
+    }
+    pub fn get_sensitivity(&mut self) -> RWResult<T, Sensitivity> {
+        Ok(registers::sensitivity_from(self.read_byte(R_SENSITIVITY)?))
+    }
+    pub fn set_base_shift(&mut self, base_shift: BaseShift) -> RWResult<T, ()> {
+        self.access(registers::base_shift(base_shift))
+    }
+    pub fn get_base_shift(&mut self) -> RWResult<T, BaseShift> {
+        Ok(BaseShift::from_bits(self.read_byte(R_SENSITIVITY)?))
+    }
+
+    /// Read the signed delta counts of all inputs
+    pub fn read_delta_values(&mut self) -> RWResult<T, ChannelValues<i8>> {
+        let mut values = ChannelValues::new(self.number_of_channels);
+        self.read_block(R_INPUT_1_DELTA, &mut values)?;
+        Ok(registers::deltas(&values))
+    }
+
+    /// Full 10-bit calibration values of the inputs, read in a single burst
+    pub fn read_calibration_values(&mut self) -> RWResult<T, ChannelValues<u16>> {
+        let mut block = [0u8; registers::CALIBRATION_LEN];
+        self.read_block(registers::CALIBRATION_START, &mut block)?;
+        Ok(registers::calibration_values(
+            &block,
+            self.number_of_channels,
+        ))
+    }
+    /// Base counts of the inputs
+    pub fn read_base_counts(&mut self) -> RWResult<T, ChannelValues<u8>> {
+        let mut values = ChannelValues::new(self.number_of_channels);
+        self.read_block(R_INPUT_1_BCOUNT, &mut values)?;
+        Ok(values)
+    }
+    /// Calibration value and base count of each input
+    pub fn read_diagnostics(&mut self) -> RWResult<T, ChannelValues<InputDiagnostics>> {
+        let calibrations = self.read_calibration_values()?;
+        let base_counts = self.read_base_counts()?;
+        Ok(registers::diagnostics(&calibrations, &base_counts))
+    }
+
+    // ----------------------------------------------------------------------------
+    // Power management
+
+    pub fn get_power_state(&mut self) -> RWResult<T, PowerState> {
+        Ok(PowerState::from_main_control(
+            self.read_byte(R_MAIN_CONTROL)?,
+        ))
+    }
+    /// Stop sampling all inputs until woken by the host or the WAKE pin
+    pub fn enter_deep_sleep(&mut self) -> RWResult<T, ()> {
+        self.access(registers::deep_sleep(true))
+    }
+    pub fn exit_deep_sleep(&mut self) -> RWResult<T, ()> {
+        self.access(registers::deep_sleep(false))
+    }
+    /// Enter deep sleep until the returned guard is dropped
+    pub fn deep_sleep(&mut self) -> RWResult<T, DeepSleep<'_, T>> {
+        DeepSleep::enter(self)
+    }
+    /// Assert the WAKE pin when a touch is detected in standby
+    pub fn assert_wake_on_standby_touch(&mut self, value: bool) -> RWResult<T, ()> {
+        self.access(registers::wake_on_standby_touch(value))
+    }
 
-    pub fn read_threshold_values(&mut self) -> Result<Vec<u8>, ReadError<<T as WriteRead>::Error>> {
-        self.read_block(R_INPUT_1_THRESH, self.number_of_leds as usize)
+    /// Configure the inputs sampled in standby and how they are sampled
+    pub fn configure_standby(&mut self, config: &StandbyConfig) -> RWResult<T, ()> {
+        self.write_block(R_STANDBY_CHANNEL, &config.to_registers())
     }
-    pub fn read_delta_values(&mut self) -> Result<Vec<u8>, ReadError<<T as WriteRead>::Error>> {
-        self.read_block(R_INPUT_1_THRESH, self.number_of_leds as usize)
+    pub fn get_standby_config(&mut self) -> RWResult<T, StandbyConfig> {
+        let mut registers = [0u8; 4];
+        self.read_block(R_STANDBY_CHANNEL, &mut registers)?;
+        Ok(StandbyConfig::from_registers(registers))
+    }
+    /// Only sample the inputs enabled for standby
+    pub fn enter_standby(&mut self) -> RWResult<T, ()> {
+        self.access(registers::standby(true))
+    }
+    /// Resume sampling all enabled inputs
+    pub fn exit_standby(&mut self) -> RWResult<T, ()> {
+        self.access(registers::standby(false))
+    }
+    pub fn is_in_standby(&mut self) -> RWResult<T, bool> {
+        Ok(registers::in_standby(self.read_byte(R_MAIN_CONTROL)?))
     }
 
     // ----------------------------------------------------------------------------
     // LEDS handling
     pub fn set_led_linking(&mut self, led_index: u8, state: bool) -> RWResult<T, ()> {
-        if led_index >= self.number_of_leds {
-            Err(Error::LedNumberOverflowError)
-        } else {
-            self.change_bit(R_LED_LINKING, led_index, state)
-        }
+        let access = registers::led_bit(R_LED_LINKING, led_index, state, self.number_of_leds)?;
+        self.access(access)
     }
     pub fn set_led_output_type(&mut self, led_index: u8, state: bool) -> RWResult<T, ()> {
-        if led_index >= self.number_of_leds {
-            Err(Error::LedNumberOverflowError)
-        } else {
-            self.change_bit(R_LED_OUTPUT_TYPE, led_index, state)
-        }
+        let access = registers::led_bit(R_LED_OUTPUT_TYPE, led_index, state, self.number_of_leds)?;
+        self.access(access)
     }
     pub fn set_led_state(&mut self, led_index: u8, state: bool) -> RWResult<T, ()> {
-        if led_index >= self.number_of_leds {
-            Err(Error::LedNumberOverflowError)
-        } else {
-            self.change_bit(R_LED_OUTPUT_CON, led_index, state)
-        }
+        let access = registers::led_bit(R_LED_OUTPUT_CON, led_index, state, self.number_of_leds)?;
+        self.access(access)
     }
     pub fn set_led_polarity(&mut self, led_index: u8, state: bool) -> RWResult<T, ()> {
-        if led_index >= self.number_of_leds {
-            Err(Error::LedNumberOverflowError)
-        } else {
-            self.change_bit(R_LED_POLARITY, led_index, state)
-        }
+        let access = registers::led_bit(R_LED_POLARITY, led_index, state, self.number_of_leds)?;
+        self.access(access)
+    }
+    /// Set the output of all LEDs in a single write, bit n driving LED n
+    pub fn set_led_states(&mut self, states: u8) -> RWResult<T, ()> {
+        self.access(registers::led_states(states, self.number_of_leds))
     }
     /// Set the behaviour of a LED
     pub fn set_led_behaviour(&mut self, led_index: u8, value: u8) -> RWResult<T, ()> {
-        if led_index >= self.number_of_leds {
-            Err(Error::LedNumberOverflowError)
-        } else {
-            let offset = led_index * 2 % 8;
-            let register = led_index / 4;
-            let value = value & 3;
-            self.change_bits(R_LED_BEHAVIOUR_1 + register, offset, 2, value)
-        }
+        let access = registers::led_behaviour(led_index, value, self.number_of_leds)?;
+        self.access(access)
     }
     pub fn convert_duration_to_period_value(period: Duration) -> u8 {
-        ((min(4064, period.as_millis()) / 32) & 127) as u8
+        registers::duration_to_period_value(period)
     }
 
     /// Set the overall period of a pulse from 32ms to 4.064 seconds
     pub fn set_led_pulse1_period(&mut self, period: Duration) -> RWResult<T, ()> {
-        self.change_bits(
-            R_LED_PULSE_1_PER,
-            0,
-            7,
-            Self::convert_duration_to_period_value(period),
-        )
+        self.access(registers::led_period(R_LED_PULSE_1_PER, period))
     }
     /// Set the overall period of a pulse from 32ms to 4.064 seconds
     pub fn set_led_pulse2_period(&mut self, period: Duration) -> RWResult<T, ()> {
-        self.change_bits(
-            R_LED_PULSE_2_PER,
-            0,
-            7,
-            Self::convert_duration_to_period_value(period),
-        )
+        self.access(registers::led_period(R_LED_PULSE_2_PER, period))
     }
     pub fn set_led_breathe_period(&mut self, period: Duration) -> RWResult<T, ()> {
-        self.change_bits(
-            R_LED_BREATHE_PER,
-            0,
-            7,
-            Self::convert_duration_to_period_value(period),
-        )
+        self.access(registers::led_period(R_LED_BREATHE_PER, period))
     }
+    /// Set the number of pulses of the pulse 1 behaviour, 1 to 8
     pub fn set_led_pulse1_count(&mut self, count: u8) -> RWResult<T, ()> {
-        self.change_bits(R_LED_CONFIG, 0, 3, (count - 1) & 7)
+        self.access(registers::led_pulse_count(0, count)?)
     }
+    /// Set the number of pulses of the pulse 2 behaviour, 1 to 8
     pub fn set_led_pulse2_count(&mut self, count: u8) -> RWResult<T, ()> {
-        self.change_bits(R_LED_CONFIG, 3, 3, (count - 1) & 7)
+        self.access(registers::led_pulse_count(3, count)?)
     }
     pub fn set_led_ramp_alert(&mut self, value: bool) -> RWResult<T, ()> {
-        self.change_bit(R_LED_CONFIG, 6, value)
+        self.access(registers::led_ramp_alert(value))
     }
 
     /// Set the rise/fall rate in ms, max 2000.
@@ -297,72 +659,1070 @@ where
     //
     //  Valid values are 0, 250, 500, 750, 1000, 1250, 1500, 2000
     pub fn set_led_direct_ramp_rate(&mut self, rise_rate: u16, fall_rate: u16) -> RWResult<T, ()> {
-        let rise_rate = rise_rate / 250;
-        let fall_rate = fall_rate / 250;
-        let rise_rate = min(7, rise_rate);
-        let fall_rate = min(7, fall_rate);
-        let rate = rise_rate << 4 | fall_rate;
-        Ok(self.write_byte(R_LED_DIRECT_RAMP, rate as u8)?)
+        self.access(registers::led_direct_ramp_rate(rise_rate, fall_rate))
     }
     pub fn set_led_direct_duty(&mut self, duty_min: u8, duty_max: u8) -> RWResult<T, ()> {
-        let value = duty_max << 4 | duty_min;
-        Ok(self.write_byte(R_LED_DIRECT_DUT, value)?)
+        self.access(registers::led_duty(R_LED_DIRECT_DUT, duty_min, duty_max))
     }
     pub fn set_led_pulse1_duty(&mut self, duty_min: u8, duty_max: u8) -> RWResult<T, ()> {
-        let value = duty_max << 4 | duty_min;
-        Ok(self.write_byte(R_LED_PULSE_1_DUT, value)?)
+        self.access(registers::led_duty(R_LED_PULSE_1_DUT, duty_min, duty_max))
     }
     pub fn set_led_pulse2_duty(&mut self, duty_min: u8, duty_max: u8) -> RWResult<T, ()> {
-        let value = duty_max << 4 | duty_min;
-        Ok(self.write_byte(R_LED_PULSE_2_DUT, value)?)
+        self.access(registers::led_duty(R_LED_PULSE_2_DUT, duty_min, duty_max))
     }
     pub fn set_led_breathe_duty(&mut self, duty_min: u8, duty_max: u8) -> RWResult<T, ()> {
-        let value = duty_max << 4 | duty_min;
-        Ok(self.write_byte(R_LED_BREATHE_DUT, value)?)
+        self.access(registers::led_duty(R_LED_BREATHE_DUT, duty_min, duty_max))
+    }
+    /// Set the duty cycles of all behaviours in a single burst write
+    pub fn set_led_duties(&mut self, duties: &LedDuties) -> RWResult<T, ()> {
+        self.write_block(R_LED_PULSE_1_DUT, &duties.to_registers())
+    }
+    pub fn get_led_duties(&mut self) -> RWResult<T, LedDuties> {
+        let mut registers = [0u8; 4];
+        self.read_block(R_LED_PULSE_1_DUT, &mut registers)?;
+        Ok(LedDuties::from_registers(registers))
     }
     pub fn set_led_direct_min_duty(&mut self, value: u8) -> RWResult<T, ()> {
-        self.change_bits(R_LED_DIRECT_DUT, 0, 4, value)
+        self.access(registers::led_min_duty(R_LED_DIRECT_DUT, value))
     }
     pub fn set_led_direct_max_duty(&mut self, value: u8) -> RWResult<T, ()> {
-        self.change_bits(R_LED_DIRECT_DUT, 4, 4, value)
+        self.access(registers::led_max_duty(R_LED_DIRECT_DUT, value))
     }
     pub fn set_led_breathe_min_duty(&mut self, value: u8) -> RWResult<T, ()> {
-        self.change_bits(R_LED_BREATHE_DUT, 0, 4, value)
+        self.access(registers::led_min_duty(R_LED_BREATHE_DUT, value))
     }
     pub fn set_led_breathe_max_duty(&mut self, value: u8) -> RWResult<T, ()> {
-        self.change_bits(R_LED_BREATHE_DUT, 4, 4, value)
+        self.access(registers::led_max_duty(R_LED_BREATHE_DUT, value))
     }
     pub fn set_led_pulse1_min_duty(&mut self, value: u8) -> RWResult<T, ()> {
-        self.change_bits(R_LED_PULSE_1_DUT, 0, 4, value)
+        self.access(registers::led_min_duty(R_LED_PULSE_1_DUT, value))
     }
     pub fn set_led_pulse1_max_duty(&mut self, value: u8) -> RWResult<T, ()> {
-        self.change_bits(R_LED_PULSE_1_DUT, 4, 4, value)
+        self.access(registers::led_max_duty(R_LED_PULSE_1_DUT, value))
     }
     pub fn set_led_pulse2_min_duty(&mut self, value: u8) -> RWResult<T, ()> {
-        self.change_bits(R_LED_PULSE_2_DUT, 0, 4, value)
+        self.access(registers::led_min_duty(R_LED_PULSE_2_DUT, value))
     }
     pub fn set_led_pulse2_max_duty(&mut self, value: u8) -> RWResult<T, ()> {
-        self.change_bits(R_LED_PULSE_2_DUT, 4, 4, value)
+        self.access(registers::led_max_duty(R_LED_PULSE_2_DUT, value))
     }
 }
 
-// internal trait to help sending commands to our device
-trait CmdWrite<T: Write> {
-    fn cmd_write(&mut self, i2c_address: u8, command: u8, buf: &[u8]) -> Result<(), T::Error>;
-}
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mock::Emulator;
 
-impl<T: Write> CmdWrite<T> for T {
-    fn cmd_write(&mut self, i2c_address: u8, command: u8, buffer: &[u8]) -> Result<(), T::Error> {
-        // *r is really shitty
-        let to_send: Vec<u8> = [command].iter().chain(buffer).map(|r| *r).collect();
-        self.write(i2c_address, &to_send)
+    fn device() -> (CAP1XXX<Emulator>, Emulator) {
+        let emulator = Emulator::new();
+        let mut cap = CAP1XXX::new(emulator.clone(), DEFAULT_ADDR, 6);
+        cap.init().unwrap();
+        (cap, emulator)
+    }
+
+    #[test]
+    fn init_applies_defaults() {
+        let (mut cap, emulator) = device();
+        assert_eq!(emulator.register(R_INPUT_ENABLE), 0b11111111);
+        assert_eq!(emulator.register(R_INTERRUPT_EN), 0b11111111);
+        assert_eq!(emulator.register(R_REPEAT_EN), 0);
+        assert_eq!(emulator.register(R_MTOUCH_CONFIG) & 0x80, 0);
+        assert_eq!(emulator.register(R_INPUT_CONFIG) & 0b1111, 5);
+        assert_eq!(emulator.register(R_INPUT_CONFIG2) & 0b1111, 5);
+        assert_eq!(emulator.register(R_SAMPLING_CONFIG), 0b00001000);
+        assert_eq!(emulator.register(R_SENSITIVITY), 0b01100000);
+        assert_eq!(emulator.register(R_GENERAL_CONFIG), 0b00111000);
+        assert_eq!(emulator.register(R_CONFIGURATION2), 0b01100000);
+        assert_eq!(cap.get_product_id().unwrap(), PID_CAP1166);
+    }
+
+    #[test]
+    fn init_with_config() {
+        let emulator = Emulator::new();
+        let mut cap = CAP1XXX::new(emulator.clone(), DEFAULT_ADDR, 6);
+        let config = Config::default()
+            .inputs(0b000111)
+            .interrupts(0b000011)
+            .repeat(0b000001)
+            .multitouch(false)
+            .hold_delay(Duration::from_millis(350))
+            .repeat_rate(Duration::from_millis(105))
+            .sampling(SamplingConfig::default())
+            .sensitivity(Sensitivity::X8)
+            .base_shift(BaseShift::X256)
+            .calibration(0b000111)
+            .general_config(0b00100000)
+            .configuration2(0b01000000);
+        cap.init_with(&config).unwrap();
+        assert_eq!(cap.get_inputs_enabled().unwrap(), 0b000111);
+        assert_eq!(cap.get_interrupts_enabled().unwrap(), 0b000011);
+        assert_eq!(cap.get_repeat_enabled().unwrap(), 0b000001);
+        assert_eq!(emulator.register(R_MTOUCH_CONFIG) & 0x80, 0x80);
+        assert_eq!(cap.get_hold_delay().unwrap(), Duration::from_millis(350));
+        assert_eq!(cap.get_repeat_rate().unwrap(), Duration::from_millis(105));
+        assert_eq!(
+            cap.get_sampling_config().unwrap(),
+            SamplingConfig::default()
+        );
+        assert_eq!(cap.get_sensitivity().unwrap(), Sensitivity::X8);
+        assert_eq!(cap.get_base_shift().unwrap(), BaseShift::X256);
+        assert_eq!(emulator.register(R_CALIBRATION), 0b000111);
+        assert_eq!(emulator.register(R_GENERAL_CONFIG), 0b00100000);
+        assert_eq!(emulator.register(R_CONFIGURATION2), 0b01000000);
+    }
+
+    #[test]
+    fn detect_models() {
+        for &(product_id, model, channels, leds) in &[
+            (PID_CAP1166, ChipModel::Cap1166, 6, 6),
+            (PID_CAP1188, ChipModel::Cap1188, 8, 8),
+            (PID_CAP1208, ChipModel::Cap1208, 8, 0),
+        ] {
+            let emulator = Emulator::with_product_id(product_id);
+            let mut cap = CAP1XXX::detect(emulator, DEFAULT_ADDR).unwrap();
+            assert_eq!(cap.model(), Some(model));
+            assert_eq!(cap.number_of_channels(), channels);
+            assert_eq!(cap.number_of_leds(), leds);
+            assert_eq!(
+                cap.read_threshold_values().unwrap().len(),
+                channels as usize
+            );
+            assert_eq!(cap.get_revision().unwrap(), 0x83);
+            assert_eq!(
+                cap.probe().unwrap(),
+                DeviceInfo {
+                    model,
+                    revision: 0x83
+                }
+            );
+        }
+    }
+
+    #[test]
+    fn detect_unsupported_devices() {
+        let emulator = Emulator::with_product_id(0x42);
+        assert!(matches!(
+            CAP1XXX::detect(emulator, DEFAULT_ADDR),
+            Err(Error::UnsupportedProductError(0x42))
+        ));
+
+        let emulator = Emulator::new();
+        emulator.set_register(R_MANUFACTURER_ID, 0x01);
+        assert!(matches!(
+            CAP1XXX::detect(emulator, DEFAULT_ADDR),
+            Err(Error::UnknownManufacturerError(0x01))
+        ));
+    }
+
+    #[test]
+    fn interrupt_and_input_status() {
+        let (mut cap, emulator) = device();
+        assert!(!cap.is_interrupted().unwrap());
+
+        emulator.press(0);
+        emulator.press(5);
+        assert!(cap.is_interrupted().unwrap());
+        assert_eq!(cap.read_input_status().unwrap(), 0b100001);
+
+        emulator.release(5);
+        cap.clear_interrupt().unwrap();
+        assert_eq!(cap.read_input_status().unwrap(), 0b000001);
+
+        emulator.release(0);
+        assert!(cap.is_interrupted().unwrap());
+        cap.clear_interrupt().unwrap();
+        assert!(!cap.is_interrupted().unwrap());
+        assert_eq!(cap.read_input_status().unwrap(), 0);
+    }
+
+    #[test]
+    fn status() {
+        let (mut cap, emulator) = device();
+        assert_eq!(cap.read_status().unwrap(), Status::default());
+
+        emulator.press(3);
+        emulator.set_register(R_NOISE_FLAG_STATUS, 0b0000_0100);
+        let transactions = emulator.transaction_count();
+        let status = cap.read_status().unwrap();
+        assert_eq!(emulator.transaction_count(), transactions + 1);
+        assert_eq!(status.inputs, 0b1000);
+        assert_eq!(status.noise, 0b0100);
+        assert_eq!(
+            status.general,
+            GeneralStatus {
+                touch: true,
+                ..GeneralStatus::default()
+            }
+        );
+        assert_eq!(cap.read_noise_flags().unwrap(), 0b0100);
+
+        emulator.set_register(R_GENERAL_STATUS, 0b0111_1110);
+        let general = cap.read_general_status().unwrap();
+        assert!(general.base_count_out_of_limit);
+        assert!(general.calibration_failed);
+        assert!(general.is_calibration_error());
+        assert!(general.power_button && general.led);
+        assert!(general.multitouch_blocked && general.pattern_detected);
+        assert!(!general.touch);
+    }
+
+    /// Counts the delays, touching an input on the `press_at`th one
+    struct TouchLater {
+        emulator: Emulator,
+        channel: u8,
+        press_at: u32,
+        delays: u32,
+        ns: u64,
+    }
+
+    impl DelayNs for TouchLater {
+        fn delay_ns(&mut self, ns: u32) {
+            self.delays += 1;
+            self.ns += u64::from(ns);
+            if self.delays == self.press_at {
+                self.emulator.press(self.channel);
+            }
+        }
+    }
+
+    #[test]
+    fn wait_for_touch_on_alert_pin() {
+        let (mut cap, emulator) = device();
+        let mut alert = emulator.alert_pin();
+        let interval = Duration::from_millis(5);
+        let mut delay = TouchLater {
+            emulator: emulator.clone(),
+            channel: 3,
+            press_at: 0,
+            delays: 0,
+            ns: 0,
+        };
+        assert!(cap.is_alert_active_low().unwrap());
+        assert!(alert.is_high().unwrap());
+
+        emulator.press(3);
+        assert!(alert.is_low().unwrap());
+        assert_eq!(
+            cap.wait_for_touch(&mut alert, interval, &mut delay)
+                .unwrap(),
+            0b1000
+        );
+        assert!(!emulator.is_alert_asserted());
+        assert_eq!(delay.delays, 0);
+
+        cap.set_alert_active_low(false).unwrap();
+        assert_eq!(emulator.register(R_CONFIGURATION2), 0b00100000);
+        assert!(alert.is_low().unwrap());
+        delay.channel = 1;
+        delay.press_at = 3;
+        assert_eq!(
+            cap.wait_for_touch(&mut alert, interval, &mut delay)
+                .unwrap(),
+            0b1010
+        );
+        assert!(!emulator.is_alert_asserted());
+        assert_eq!(delay.delays, 3);
+        assert_eq!(delay.ns, 15_000_000);
+    }
+
+    #[test]
+    fn alert_pin_errors_are_reported() {
+        struct BrokenPin;
+        impl embedded_hal::digital::ErrorType for BrokenPin {
+            type Error = embedded_hal::digital::ErrorKind;
+        }
+        impl InputPin for BrokenPin {
+            fn is_high(&mut self) -> Result<bool, Self::Error> {
+                Err(embedded_hal::digital::ErrorKind::Other)
+            }
+            fn is_low(&mut self) -> Result<bool, Self::Error> {
+                Err(embedded_hal::digital::ErrorKind::Other)
+            }
+        }
+        let (mut cap, emulator) = device();
+        let mut delay = TouchLater {
+            emulator,
+            channel: 0,
+            press_at: 0,
+            delays: 0,
+            ns: 0,
+        };
+        assert!(matches!(
+            cap.wait_for_touch(&mut BrokenPin, Duration::from_millis(1), &mut delay),
+            Err(Error::AlertPinError(
+                embedded_hal::digital::ErrorKind::Other
+            ))
+        ));
+    }
+
+    #[test]
+    fn input_configuration() {
+        let (mut cap, emulator) = device();
+        cap.enable_inputs(0b0101).unwrap();
+        cap.enable_interrupts(0b0100).unwrap();
+        cap.enable_repeat(0b0010).unwrap();
+        cap.enable_multitouch(false).unwrap();
+        assert_eq!(emulator.register(R_INPUT_ENABLE), 0b0101);
+        assert_eq!(emulator.register(R_INTERRUPT_EN), 0b0100);
+        assert_eq!(emulator.register(R_REPEAT_EN), 0b0010);
+        assert_eq!(emulator.register(R_MTOUCH_CONFIG) & 0x80, 0x80);
+
+        emulator.press(0);
+        assert!(!cap.is_interrupted().unwrap());
+        emulator.press(1);
+        assert_eq!(cap.read_input_status().unwrap(), 0b0001);
+        emulator.press(2);
+        assert!(cap.is_interrupted().unwrap());
+    }
+
+    #[test]
+    fn touch_limit() {
+        let (mut cap, emulator) = device();
+        assert_eq!(cap.get_touch_limit().unwrap(), TouchLimit::Touches1);
+        cap.enable_multitouch(false).unwrap();
+        cap.set_touch_limit(TouchLimit::from_count(2)).unwrap();
+        assert_eq!(emulator.register(R_MTOUCH_CONFIG), 0b1000_0100);
+        assert_eq!(cap.get_touch_limit().unwrap().count(), 2);
+        assert_eq!(TouchLimit::from_count(0), TouchLimit::Touches1);
+        assert_eq!(TouchLimit::from_count(9), TouchLimit::Touches4);
+
+        emulator.press(0);
+        emulator.press(1);
+        assert!(!cap.is_multitouch_blocked().unwrap());
+        emulator.press(2);
+        assert!(cap.is_multitouch_blocked().unwrap());
+        emulator.release(2);
+        assert!(cap.is_multitouch_blocked().unwrap());
+        cap.clear_interrupt().unwrap();
+        assert!(!cap.is_multitouch_blocked().unwrap());
+
+        // no blocking when multi-touch is enabled
+        cap.enable_multitouch(true).unwrap();
+        assert_eq!(cap.get_touch_limit().unwrap(), TouchLimit::Touches2);
+        emulator.press(2);
+        assert!(!cap.is_multitouch_blocked().unwrap());
+    }
+
+    #[test]
+    fn multiple_touch_pattern() {
+        let (mut cap, emulator) = device();
+        let pattern = cap.get_multiple_touch_pattern().unwrap();
+        assert!(!pattern.enabled);
+        assert_eq!(pattern.threshold, PatternThreshold::Percent12_5);
+        assert_eq!(pattern.mode, PatternMode::Count);
+        assert_eq!(pattern.inputs, 0b0011_1111);
+
+        let palm = MultipleTouchPattern {
+            enabled: true,
+            mode: PatternMode::Count,
+            threshold: PatternThreshold::Percent37_5,
+            alert: true,
+            inputs: 0b0000_0111,
+        };
+        cap.configure_multiple_touch_pattern(&palm).unwrap();
+        assert_eq!(emulator.register(R_MTOUCH_PAT_CONF), 0b1000_1001);
+        assert_eq!(emulator.register(R_MTOUCH_PATTERN), 0b0000_0111);
+        assert_eq!(cap.get_multiple_touch_pattern().unwrap(), palm);
+
+        // only the pattern raises interrupts
+        cap.enable_interrupts(0).unwrap();
+        emulator.press(3);
+        emulator.press(5);
+        assert!(!cap.is_multiple_touch_pattern_detected().unwrap());
+        assert!(!cap.is_interrupted().unwrap());
+        emulator.press(0);
+        assert!(cap.is_multiple_touch_pattern_detected().unwrap());
+        assert!(cap.is_interrupted().unwrap());
+
+        // latched until the interrupt is cleared
+        emulator.release(5);
+        assert!(cap.is_multiple_touch_pattern_detected().unwrap());
+        cap.clear_interrupt().unwrap();
+        assert!(!cap.is_multiple_touch_pattern_detected().unwrap());
+
+        cap.configure_multiple_touch_pattern(&MultipleTouchPattern {
+            mode: PatternMode::Pattern,
+            inputs: 0b0010_0001,
+            ..palm
+        })
+        .unwrap();
+        assert_eq!(emulator.register(R_MTOUCH_PAT_CONF), 0b1000_1011);
+        emulator.press(1);
+        assert!(!cap.is_multiple_touch_pattern_detected().unwrap());
+        emulator.press(5);
+        assert!(cap.is_multiple_touch_pattern_detected().unwrap());
+    }
+
+    #[test]
+    fn general_configuration() {
+        let (mut cap, emulator) = device();
+        cap.auto_recalibrate(false).unwrap();
+        cap.filter_analog_noise(true).unwrap();
+        cap.filter_digital_noise(true).unwrap();
+        assert_eq!(emulator.register(R_GENERAL_CONFIG), 0);
+        cap.auto_recalibrate(true).unwrap();
+        cap.filter_analog_noise(false).unwrap();
+        cap.filter_digital_noise(false).unwrap();
+        assert_eq!(emulator.register(R_GENERAL_CONFIG), 0b00111000);
+
+        cap.calibrate(0xFF).unwrap();
+        assert_eq!(emulator.register(R_CALIBRATION), 0b0011_1111);
+        cap.calibrate(0b100).unwrap();
+        assert_eq!(emulator.register(R_CALIBRATION), 0b100);
+    }
+
+    /// Counts the delays, restarting the calibration of `restart` inputs on each of them
+    struct Polls {
+        emulator: Emulator,
+        restart: u8,
+        count: u32,
+    }
+
+    impl DelayNs for Polls {
+        fn delay_ns(&mut self, _ns: u32) {
+            self.count += 1;
+            let pending = self.emulator.register(R_CALIBRATION);
+            self.emulator
+                .set_register(R_CALIBRATION, pending | self.restart);
+        }
+    }
+
+    #[test]
+    fn calibration_completion() {
+        let (mut cap, emulator) = device();
+        let timeout = Duration::from_millis(100);
+        let mut polls = Polls {
+            emulator: emulator.clone(),
+            restart: 0,
+            count: 0,
+        };
+        cap.calibrate(0b11).unwrap();
+        assert_eq!(
+            cap.wait_calibration_done(0b11, timeout, &mut polls)
+                .unwrap(),
+            0
+        );
+        assert_eq!(polls.count, 1);
+        assert_eq!(emulator.register(R_CALIBRATION), 0);
+
+        // ACAL_FAIL: the failed inputs keep their CALIBRATION_ACTIVATE bit
+        emulator.fail_calibration(0b10);
+        cap.calibrate(0b111).unwrap();
+        assert_eq!(
+            cap.wait_calibration_done(0b111, timeout, &mut polls)
+                .unwrap(),
+            0b10
+        );
+        assert!(cap.read_general_status().unwrap().calibration_failed);
+        // ACAL_FAIL is latched until the interrupt is cleared
+        emulator.fail_calibration(0);
+        cap.calibrate(0b10).unwrap();
+        cap.clear_interrupt().unwrap();
+        assert!(!cap.read_general_status().unwrap().calibration_failed);
+        assert_eq!(
+            cap.wait_calibration_done(0b10, timeout, &mut polls)
+                .unwrap(),
+            0
+        );
+        // only the waited inputs are reported
+        emulator.fail_calibration(0b10);
+        cap.calibrate(0b111).unwrap();
+        assert_eq!(
+            cap.wait_calibration_done(0b1, timeout, &mut polls).unwrap(),
+            0
+        );
+        emulator.fail_calibration(0);
+        cap.calibrate(0b10).unwrap();
+        cap.clear_interrupt().unwrap();
+
+        // BC_OUT
+        emulator.set_register(R_GENERAL_STATUS, 0x40);
+        emulator.set_register(R_COUNT_O_LIMIT, 0b1001);
+        cap.calibrate(0b11).unwrap();
+        assert_eq!(
+            cap.wait_calibration_done(0b11, timeout, &mut polls)
+                .unwrap(),
+            0b1
+        );
+        emulator.set_register(R_GENERAL_STATUS, 0);
+
+        // 10ms polls: 10 delays before giving up after 100ms
+        polls.restart = 0b1;
+        polls.count = 0;
+        cap.calibrate(0b1).unwrap();
+        assert!(matches!(
+            cap.wait_calibration_done(0b1, timeout, &mut polls),
+            Err(Error::CalibrationTimeout)
+        ));
+        assert_eq!(polls.count, 10);
+    }
+
+    #[test]
+    fn hold_delay_and_repeat_rate() {
+        let (mut cap, emulator) = device();
+        cap.set_hold_delay(Duration::from_millis(560)).unwrap();
+        cap.set_repeat_rate(Duration::from_millis(10)).unwrap();
+        assert_eq!(emulator.register(R_INPUT_CONFIG2), 0x0F);
+        assert_eq!(emulator.register(R_INPUT_CONFIG), 0xA0);
+        assert_eq!(cap.get_hold_delay().unwrap(), Duration::from_millis(560));
+        assert_eq!(cap.get_repeat_rate().unwrap(), Duration::from_millis(35));
+
+        assert_eq!(
+            CAP1XXX::<Emulator>::duration_to_rate_scale(Duration::from_millis(0)),
+            0
+        );
+        assert_eq!(
+            CAP1XXX::<Emulator>::duration_to_rate_scale(Duration::from_millis(104)),
+            1
+        );
+        assert_eq!(
+            CAP1XXX::<Emulator>::duration_to_rate_scale(Duration::from_secs(1)),
+            15
+        );
+    }
+
+    #[test]
+    fn thresholds() {
+        let (mut cap, emulator) = device();
+        emulator.set_register(R_INPUT_3_THRESH, 0x20);
+        assert_eq!(
+            cap.read_threshold_values().unwrap()[..],
+            [0x40, 0x40, 0x20, 0x40, 0x40, 0x40]
+        );
+    }
+
+    #[test]
+    fn set_thresholds() {
+        let (mut cap, emulator) = device();
+        cap.set_threshold(0, 0x10).unwrap();
+        assert_eq!(cap.read_threshold_values().unwrap()[..], [0x10; 6]);
+
+        cap.link_thresholds(false).unwrap();
+        cap.set_threshold(0, 0x20).unwrap();
+        cap.set_threshold(5, 0x80).unwrap();
+        cap.set_threshold(4, 200).unwrap();
+        assert_eq!(
+            cap.read_threshold_values().unwrap()[..],
+            [0x20, 0x10, 0x10, 0x10, 0x7F, 0x7F]
+        );
+        assert!(matches!(
+            cap.set_threshold(6, 0x10),
+            Err(Error::ChannelNumberOverflowError)
+        ));
+
+        cap.link_thresholds(true).unwrap();
+        cap.set_thresholds(&[1, 2, 3]).unwrap();
+        assert_eq!(cap.read_threshold_values().unwrap()[..], [1, 2, 3, 1, 1, 1]);
+        assert_eq!(emulator.register(R_INPUT_7_THRESH), 1);
+        cap.link_thresholds(false).unwrap();
+        cap.set_thresholds(&[0x80, 0xC8]).unwrap();
+        assert_eq!(
+            cap.read_threshold_values().unwrap()[..],
+            [0x7F, 0x7F, 3, 1, 1, 1]
+        );
+        assert!(matches!(
+            cap.set_thresholds(&[0; 7]),
+            Err(Error::ChannelNumberOverflowError)
+        ));
+    }
+
+    #[test]
+    fn noise_threshold() {
+        let (mut cap, emulator) = device();
+        assert_eq!(
+            cap.get_noise_threshold().unwrap(),
+            NoiseThreshold::Percent37_5
+        );
+        cap.set_noise_threshold(NoiseThreshold::Percent62_5)
+            .unwrap();
+        assert_eq!(emulator.register(R_NOISE_THRESH), 0b11);
+        cap.set_noise_threshold(NoiseThreshold::Percent25).unwrap();
+        assert_eq!(
+            cap.get_noise_threshold().unwrap(),
+            NoiseThreshold::Percent25
+        );
+    }
+
+    #[test]
+    fn sampling_config() {
+        let (mut cap, emulator) = device();
+        let config = cap.get_sampling_config().unwrap();
+        assert_eq!(config.averaging.samples(), 1);
+        assert_eq!(config.sample_time.duration(), Duration::from_micros(1280));
+        assert_eq!(config.cycle_time.duration(), Duration::from_millis(35));
+
+        cap.set_sampling_config(&SamplingConfig::default()).unwrap();
+        assert_eq!(emulator.register(R_SAMPLING_CONFIG), 0x39);
+
+        let config = SamplingConfig {
+            averaging: Averaging::Samples128,
+            sample_time: SampleTime::Us320,
+            cycle_time: CycleTime::Ms140,
+        };
+        cap.set_sampling_config(&config).unwrap();
+        assert_eq!(emulator.register(R_SAMPLING_CONFIG), 0b0111_0011);
+        assert_eq!(cap.get_sampling_config().unwrap(), config);
+    }
+
+    #[test]
+    fn sensitivity_and_base_shift() {
+        let (mut cap, emulator) = device();
+        assert_eq!(cap.get_sensitivity().unwrap(), Sensitivity::X2);
+        assert_eq!(cap.get_base_shift().unwrap(), BaseShift::X1);
+
+        cap.set_sensitivity(Sensitivity::X128).unwrap();
+        cap.set_base_shift(BaseShift::X256).unwrap();
+        assert_eq!(emulator.register(R_SENSITIVITY), 0b0000_1000);
+        cap.set_sensitivity(Sensitivity::X16).unwrap();
+        assert_eq!(cap.get_sensitivity().unwrap(), Sensitivity::X16);
+        assert_eq!(cap.get_sensitivity().unwrap().multiplier(), 16);
+        assert_eq!(Sensitivity::from_multiplier(16), Some(Sensitivity::X16));
+        assert_eq!(Sensitivity::from_multiplier(128), Some(Sensitivity::X128));
+        assert_eq!(Sensitivity::from_multiplier(12), None);
+        assert_eq!(Sensitivity::from_multiplier(0), None);
+
+        emulator.set_register(R_SENSITIVITY, 0b0000_1111);
+        assert_eq!(cap.get_base_shift().unwrap(), BaseShift::X256);
+        assert_eq!(BaseShift::X256.multiplier(), 256);
+        assert_eq!(BaseShift::X1.multiplier(), 1);
+        assert_eq!(NoiseThreshold::Percent25.fraction(), 0.25);
+        assert_eq!(NoiseThreshold::Percent62_5.fraction(), 0.625);
+        assert_eq!(PatternThreshold::Percent37_5.fraction(), 0.375);
+        assert_eq!(PatternThreshold::Percent100.fraction(), 1.0);
+    }
+
+    #[test]
+    fn standby() {
+        let (mut cap, emulator) = device();
+        assert_eq!(cap.get_standby_config().unwrap(), StandbyConfig::default());
+
+        let config = StandbyConfig {
+            channels: 0b000100,
+            summation: true,
+            averaging: Averaging::Samples16,
+            sample_time: SampleTime::Us640,
+            cycle_time: CycleTime::Ms140,
+            sensitivity: Sensitivity::X64,
+            threshold: 0x30,
+        };
+        cap.configure_standby(&config).unwrap();
+        assert_eq!(emulator.register(R_STANDBY_CHANNEL), 0b000100);
+        assert_eq!(emulator.register(R_STANDBY_CONFIG), 0b1100_0111);
+        assert_eq!(emulator.register(R_STANDBY_SENS), 0b001);
+        assert_eq!(emulator.register(R_STANDBY_THRESH), 0x30);
+        assert_eq!(cap.get_standby_config().unwrap(), config);
+        cap.configure_standby(&StandbyConfig {
+            threshold: 0x80,
+            ..config
+        })
+        .unwrap();
+        assert_eq!(emulator.register(R_STANDBY_THRESH), 0x7F);
+
+        emulator.press(0);
+        cap.enter_standby().unwrap();
+        assert!(cap.is_in_standby().unwrap());
+        // entering standby must not clear a pending interrupt
+        assert!(cap.is_interrupted().unwrap());
+        emulator.release(0);
+        cap.clear_interrupt().unwrap();
+
+        emulator.press(1);
+        assert!(!cap.is_interrupted().unwrap());
+        emulator.press(2);
+        assert!(cap.is_interrupted().unwrap());
+        assert_eq!(cap.read_input_status().unwrap(), 0b100);
+
+        cap.exit_standby().unwrap();
+        assert!(!cap.is_in_standby().unwrap());
+    }
+
+    #[test]
+    fn deep_sleep() {
+        let (mut cap, emulator) = device();
+        assert_eq!(cap.get_power_state().unwrap(), PowerState::Active);
+        cap.enter_standby().unwrap();
+        assert_eq!(cap.get_power_state().unwrap(), PowerState::Standby);
+
+        {
+            let mut sleeping = cap.deep_sleep().unwrap();
+            assert_eq!(sleeping.get_power_state().unwrap(), PowerState::DeepSleep);
+            sleeping.enable_inputs(0).unwrap();
+            sleeping.enable_interrupts(0).unwrap();
+            emulator.press(0);
+            assert!(!emulator.is_alert_asserted());
+        }
+        assert_eq!(cap.get_power_state().unwrap(), PowerState::Standby);
+        assert_eq!(cap.get_inputs_enabled().unwrap(), 0xFF);
+        assert_eq!(cap.get_interrupts_enabled().unwrap(), 0xFF);
+
+        cap.exit_standby().unwrap();
+        cap.enable_inputs(0b11).unwrap();
+        let sleeping = cap.deep_sleep().unwrap();
+        assert_eq!(emulator.register(R_MAIN_CONTROL), 0x10);
+        sleeping.wake().unwrap();
+        assert_eq!(cap.get_power_state().unwrap(), PowerState::Active);
+        assert_eq!(cap.get_inputs_enabled().unwrap(), 0b11);
+    }
+
+    #[test]
+    fn wake_pin() {
+        let (mut cap, emulator) = device();
+        cap.assert_wake_on_standby_touch(true).unwrap();
+        assert_eq!(emulator.register(R_GENERAL_CONFIG), 0b01111000);
+        cap.assert_wake_on_standby_touch(false).unwrap();
+        assert_eq!(emulator.register(R_GENERAL_CONFIG), 0b00111000);
+    }
+
+    #[test]
+    fn delta_values() {
+        let (mut cap, emulator) = device();
+        emulator.set_register(R_INPUT_1_DELTA, 0x7F);
+        emulator.set_register(R_INPUT_2_DELTA, 0x80);
+        emulator.set_register(R_INPUT_6_DELTA, 0xFE);
+        emulator.set_register(R_INPUT_7_DELTA, 0x10);
+        assert_eq!(
+            cap.read_delta_values().unwrap()[..],
+            [127, -128, 0, 0, 0, -2]
+        );
+
+        let emulator = Emulator::with_product_id(PID_CAP1188);
+        emulator.set_register(R_INPUT_8_DELTA, 0x10);
+        let mut cap = CAP1XXX::detect(emulator, DEFAULT_ADDR).unwrap();
+        assert_eq!(cap.read_delta_values().unwrap()[7], 16);
+
+        // no more than the 8 inputs of the largest parts
+        let mut cap = CAP1XXX::new(Emulator::new(), DEFAULT_ADDR, 9);
+        assert_eq!(cap.number_of_channels(), 8);
+        assert_eq!(cap.number_of_leds(), 8);
+        assert_eq!(cap.read_threshold_values().unwrap().len(), 8);
+        assert_eq!(cap.read_delta_values().unwrap().len(), 8);
+        assert_eq!(cap.read_base_counts().unwrap().len(), 8);
+        assert_eq!(cap.read_calibration_values().unwrap().len(), 8);
+    }
+
+    #[test]
+    fn calibration_diagnostics() {
+        let (mut cap, emulator) = device();
+        emulator.set_register(R_INPUT_1_CALIB, 0xFF);
+        emulator.set_register(R_INPUT_2_CALIB, 0x80);
+        emulator.set_register(R_INPUT_5_CALIB, 0x01);
+        // 2 LSBs of inputs 1 to 4, then 5 to 8
+        emulator.set_register(R_INPUT_CAL_LSB1, 0b11_00_10_11);
+        emulator.set_register(R_INPUT_CAL_LSB2, 0b00_00_11_01);
+        emulator.set_register(R_INPUT_1_BCOUNT, 0x7D);
+        emulator.set_register(R_INPUT_6_BCOUNT, 0xC0);
+        assert_eq!(
+            cap.read_calibration_values().unwrap()[..],
+            [1023, 514, 0, 3, 5, 3]
+        );
+        assert_eq!(
+            cap.read_base_counts().unwrap()[..],
+            [0x7D, 0, 0, 0, 0, 0xC0]
+        );
+        let diagnostics = cap.read_diagnostics().unwrap();
+        assert_eq!(diagnostics.len(), 6);
+        assert_eq!(
+            diagnostics[0],
+            InputDiagnostics {
+                calibration: 1023,
+                base_count: 0x7D
+            }
+        );
+        assert_eq!(diagnostics[5].calibration, 3);
+        assert_eq!(diagnostics[5].base_count, 0xC0);
+
+        let emulator = Emulator::with_product_id(PID_CAP1188);
+        emulator.set_register(R_INPUT_8_CALIB, 0x40);
+        emulator.set_register(R_INPUT_CAL_LSB2, 0b10_00_00_00);
+        emulator.set_register(R_INPUT_8_BCOUNT, 0x30);
+        let mut cap = CAP1XXX::detect(emulator, DEFAULT_ADDR).unwrap();
+        let diagnostics = cap.read_diagnostics().unwrap();
+        assert_eq!(diagnostics.len(), 8);
+        assert_eq!(diagnostics[7].calibration, 0x102);
+        assert_eq!(diagnostics[7].base_count, 0x30);
+    }
+
+    #[test]
+    fn led_bits() {
+        let (mut cap, emulator) = device();
+        cap.set_led_linking(1, true).unwrap();
+        cap.set_led_output_type(2, true).unwrap();
+        cap.set_led_state(3, true).unwrap();
+        cap.set_led_polarity(4, true).unwrap();
+        assert_eq!(emulator.register(R_LED_LINKING), 0b00010);
+        assert_eq!(emulator.register(R_LED_OUTPUT_TYPE), 0b00100);
+        assert_eq!(emulator.register(R_LED_OUTPUT_CON), 0b01000);
+        assert_eq!(emulator.register(R_LED_POLARITY), 0b10000);
+        cap.set_led_state(3, false).unwrap();
+        assert_eq!(emulator.register(R_LED_OUTPUT_CON), 0);
+    }
+
+    #[test]
+    fn led_number_overflow() {
+        let (mut cap, _) = device();
+        assert!(matches!(
+            cap.set_led_state(6, true),
+            Err(Error::LedNumberOverflowError)
+        ));
+        assert!(matches!(
+            cap.set_led_behaviour(6, LED_BEHAVIOUR_BREATHE),
+            Err(Error::LedNumberOverflowError)
+        ));
+    }
+
+    #[test]
+    fn led_behaviour() {
+        let (mut cap, emulator) = device();
+        cap.set_led_behaviour(0, LED_BEHAVIOUR_PULSE1).unwrap();
+        cap.set_led_behaviour(3, LED_BEHAVIOUR_BREATHE).unwrap();
+        cap.set_led_behaviour(5, LED_BEHAVIOUR_PULSE2).unwrap();
+        assert_eq!(emulator.register(R_LED_BEHAVIOUR_1), 0b11_00_00_01);
+        assert_eq!(emulator.register(R_LED_BEHAVIOUR_2), 0b00_00_10_00);
+    }
+
+    #[test]
+    fn led_periods_and_counts() {
+        let (mut cap, emulator) = device();
+        cap.set_led_pulse1_period(Duration::from_millis(64))
+            .unwrap();
+        cap.set_led_pulse2_period(Duration::from_secs(10)).unwrap();
+        cap.set_led_breathe_period(Duration::from_millis(320))
+            .unwrap();
+        assert_eq!(emulator.register(R_LED_PULSE_1_PER), 2);
+        assert_eq!(emulator.register(R_LED_PULSE_2_PER), 127);
+        assert_eq!(emulator.register(R_LED_BREATHE_PER), 10);
+
+        cap.set_led_pulse1_count(3).unwrap();
+        cap.set_led_pulse2_count(8).unwrap();
+        cap.set_led_ramp_alert(true).unwrap();
+        assert_eq!(emulator.register(R_LED_CONFIG), 0b1_111_010);
+        for &count in &[0, 9] {
+            assert!(matches!(
+                cap.set_led_pulse1_count(count),
+                Err(Error::PulseCountError(c)) if c == count
+            ));
+            assert!(matches!(
+                cap.set_led_pulse2_count(count),
+                Err(Error::PulseCountError(c)) if c == count
+            ));
+        }
+        assert_eq!(emulator.register(R_LED_CONFIG), 0b1_111_010);
+    }
+
+    #[test]
+    fn led_ramp_and_duty() {
+        let (mut cap, emulator) = device();
+        cap.set_led_direct_ramp_rate(500, 5000).unwrap();
+        assert_eq!(emulator.register(R_LED_DIRECT_RAMP), 0x27);
+
+        cap.set_led_direct_duty(1, 2).unwrap();
+        cap.set_led_pulse1_duty(3, 4).unwrap();
+        cap.set_led_pulse2_duty(5, 6).unwrap();
+        cap.set_led_breathe_duty(7, 8).unwrap();
+        assert_eq!(emulator.register(R_LED_DIRECT_DUT), 0x21);
+        assert_eq!(emulator.register(R_LED_PULSE_1_DUT), 0x43);
+        assert_eq!(emulator.register(R_LED_PULSE_2_DUT), 0x65);
+        assert_eq!(emulator.register(R_LED_BREATHE_DUT), 0x87);
+
+        cap.set_led_direct_min_duty(0xA).unwrap();
+        cap.set_led_direct_max_duty(0xB).unwrap();
+        cap.set_led_pulse1_min_duty(0xC).unwrap();
+        cap.set_led_pulse1_max_duty(0xD).unwrap();
+        cap.set_led_pulse2_min_duty(0xE).unwrap();
+        cap.set_led_pulse2_max_duty(0xF).unwrap();
+        cap.set_led_breathe_min_duty(0x1).unwrap();
+        cap.set_led_breathe_max_duty(0x2).unwrap();
+        assert_eq!(emulator.register(R_LED_DIRECT_DUT), 0xBA);
+        assert_eq!(emulator.register(R_LED_PULSE_1_DUT), 0xDC);
+        assert_eq!(emulator.register(R_LED_PULSE_2_DUT), 0xFE);
+        assert_eq!(emulator.register(R_LED_BREATHE_DUT), 0x21);
+    }
+
+    #[test]
+    fn burst_writes() {
+        let (mut cap, emulator) = device();
+        let duties = LedDuties {
+            pulse1: LedDuty { min: 1, max: 2 },
+            pulse2: LedDuty { min: 3, max: 4 },
+            breathe: LedDuty { min: 5, max: 6 },
+            direct: LedDuty { min: 0, max: 15 },
+        };
+        let start = emulator.transaction_count();
+        cap.set_led_duties(&duties).unwrap();
+        cap.set_led_states(0xFF).unwrap();
+        cap.set_thresholds(&[1, 2, 3, 4, 5, 6]).unwrap();
+        assert_eq!(emulator.transaction_count() - start, 3);
+        assert_eq!(emulator.register(R_LED_PULSE_1_DUT), 0x21);
+        assert_eq!(emulator.register(R_LED_PULSE_2_DUT), 0x43);
+        assert_eq!(emulator.register(R_LED_BREATHE_DUT), 0x65);
+        assert_eq!(emulator.register(R_LED_DIRECT_DUT), 0xF0);
+        assert_eq!(emulator.register(R_LED_OUTPUT_CON), 0b111111);
+        assert_eq!(cap.get_led_duties().unwrap(), duties);
+        assert_eq!(LedDuties::default(), LedDuties::from_registers([0xF0; 4]));
+    }
+
+    #[test]
+    fn snapshot_and_restore() {
+        let (mut cap, emulator) = device();
+        emulator.set_register(R_MAIN_CONTROL, 0x80);
+        cap.set_thresholds(&[10, 20, 30, 40, 50, 60]).unwrap();
+        cap.set_sensitivity(Sensitivity::X16).unwrap();
+        cap.set_led_behaviour(4, LED_BEHAVIOUR_PULSE2).unwrap();
+        cap.set_led_pulse2_duty(2, 12).unwrap();
+        emulator.press(1);
+        emulator.set_register(R_INPUT_2_CALIB, 0x81);
+        emulator.set_register(R_INPUT_CAL_LSB1, 0b1100);
+        emulator.set_register(R_COUNT_O_LIMIT, 0b11);
+        let snapshot = cap.snapshot().unwrap();
+        assert_eq!(snapshot.get(R_INPUT_STATUS), 0b10);
+        assert_eq!(snapshot.get(R_INPUT_2_THRESH), 20);
+        assert_eq!(snapshot.get(R_PRODUCT_ID), PID_CAP1166);
+
+        let text = std::format!("{}", snapshot);
+        assert!(text.contains("MAIN_CONTROL       0x00 = 0x81  gain 4x, Active, INT 1\n"));
+        assert!(text.contains("SENSITIVITY        0x1f = 0x30  sensitivity 16x, base shift 1x\n"));
+        assert!(text.contains("LED 5: pulse 2, LED 6: direct"));
+        assert!(text.contains("PRODUCT_ID         0xfd = 0x51  Cap1166\n"));
+        assert!(text.contains("INPUT_CALIB        0xb2 = 0x81  input 2, calibration 519\n"));
+        assert!(text
+            .contains("MTOUCH_CONFIG      0x2a = 0x00  multi-touch blocked 0, limit 1 touches\n"));
+        assert!(text.contains(
+            "MTOUCH_PAT_CONF    0x2b = 0x00  enabled 0, Count mode, threshold 12.5%, alert 0\n"
+        ));
+        assert!(text.contains("NOISE_THRESH       0x38 = 0x01  37.5% of the touch threshold\n"));
+
+        let restore_position = |register: u8| {
+            snapshot::RESTORED
+                .iter()
+                .position(|(start, end)| (*start..=*end).contains(&register))
+        };
+        assert!(restore_position(R_MTOUCH_PATTERN) < restore_position(R_MTOUCH_PAT_CONF));
+
+        let (mut other, other_emulator) = device();
+        other.restore(&snapshot).unwrap();
+        let restored = other.snapshot().unwrap();
+        for (start, end) in snapshot::RESTORED.iter() {
+            assert_eq!(restored.block(*start, *end), snapshot.block(*start, *end));
+        }
+        assert_eq!(other_emulator.register(R_MAIN_CONTROL), 0x80);
+        assert_eq!(restored.get(R_INPUT_STATUS), 0);
+        assert_eq!(restored.get(R_COUNT_O_LIMIT), 0x14);
+
+        let bytes = *snapshot.as_bytes();
+        assert_eq!(bytes[R_INPUT_2_THRESH as usize], 20);
+        assert_eq!(RegisterSnapshot::from_bytes(bytes), snapshot);
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn snapshot_serde() {
+        let (mut cap, _) = device();
+        cap.set_thresholds(&[10, 20, 30]).unwrap();
+        let snapshot = cap.snapshot().unwrap();
+        let json = serde_json::to_string(&snapshot).unwrap();
+        assert_eq!(
+            serde_json::from_str::<RegisterSnapshot>(&json).unwrap(),
+            snapshot
+        );
+        assert!(serde_json::from_str::<RegisterSnapshot>("[1, 2, 3]").is_err());
+    }
+
+    #[test]
+    fn register_cache() {
+        let (mut cap, emulator) = device();
+        cap.sync_from_device().unwrap();
+        assert!(cap.is_cache_enabled());
+
+        let start = emulator.transaction_count();
+        for led in 0..6 {
+            cap.set_led_behaviour(led, LED_BEHAVIOUR_BREATHE).unwrap();
+        }
+        assert_eq!(emulator.transaction_count() - start, 6);
+        assert_eq!(emulator.register(R_LED_BEHAVIOUR_1), 0xFF);
+        assert_eq!(emulator.register(R_LED_BEHAVIOUR_2) & 0x0F, 0x0F);
+
+        // served from the cache
+        let start = emulator.transaction_count();
+        assert_eq!(cap.get_inputs_enabled().unwrap(), 0xFF);
+        assert_eq!(cap.get_hold_delay().unwrap(), Duration::from_millis(210));
+        assert_eq!(emulator.transaction_count(), start);
+
+        // volatile registers are always read from the device
+        emulator.press(2);
+        assert!(cap.is_interrupted().unwrap());
+        assert_eq!(cap.read_input_status().unwrap(), 0b100);
+        cap.clear_interrupt().unwrap();
+        assert!(!emulator.is_alert_asserted());
+
+        // changed behind the driver's back
+        emulator.set_register(R_INPUT_ENABLE, 0x0F);
+        assert_eq!(cap.get_inputs_enabled().unwrap(), 0xFF);
+        cap.invalidate_cache();
+        assert_eq!(cap.get_inputs_enabled().unwrap(), 0x0F);
+
+        cap.disable_cache();
+        emulator.set_register(R_INPUT_ENABLE, 0x03);
+        assert_eq!(cap.get_inputs_enabled().unwrap(), 0x03);
+    }
+
+    #[test]
+    fn register_cache_is_filled_lazily() {
+        let (mut cap, emulator) = device();
+        cap.enable_cache();
+        let start = emulator.transaction_count();
+        cap.set_led_state(0, true).unwrap();
+        cap.set_led_state(1, true).unwrap();
+        assert_eq!(emulator.transaction_count() - start, 3);
+        assert_eq!(emulator.register(R_LED_OUTPUT_CON), 0b11);
+
+        // linked thresholds are not cached
+        cap.set_threshold(0, 0x20).unwrap();
+        assert_eq!(cap.read_threshold_values().unwrap()[..], [0x20; 6]);
+    }
+
+    #[test]
+    fn clones_do_not_share_the_register_cache() {
+        let (mut cap, emulator) = device();
+        cap.sync_from_device().unwrap();
+        let mut other = cap.clone();
+        assert!(!other.is_cache_enabled());
+        cap.set_led_state(2, true).unwrap();
+        // read from the device, not from a copy of the cache taken before the clone
+        other.set_led_state(0, true).unwrap();
+        assert_eq!(emulator.register(R_LED_OUTPUT_CON), 0b101);
+        cap.invalidate_cache();
+        cap.set_led_state(1, true).unwrap();
+        assert_eq!(emulator.register(R_LED_OUTPUT_CON), 0b111);
+    }
+
+    #[test]
+    fn register_cache_skips_base_count_out_of_limit() {
+        let (mut cap, emulator) = device();
+        cap.enable_cache();
+        cap.sync_from_device().unwrap();
+        emulator.set_register(R_GENERAL_STATUS, 0x40);
+        emulator.set_register(R_COUNT_O_LIMIT, 0b10);
+        let mut polls = Polls {
+            emulator: emulator.clone(),
+            restart: 0,
+            count: 0,
+        };
+        cap.calibrate(0b11).unwrap();
+        assert_eq!(
+            cap.wait_calibration_done(0b11, Duration::from_millis(100), &mut polls)
+                .unwrap(),
+            0b10
+        );
     }
-}
 
-#[cfg(test)]
-mod tests {
     #[test]
-    fn it_works() {
-        assert_eq!(2 + 2, 4);
+    fn bus_errors_are_reported() {
+        let emulator = Emulator::new().at_address(0x2C);
+        let mut cap = CAP1XXX::new(emulator, DEFAULT_ADDR, 6);
+        assert!(matches!(
+            cap.init(),
+            Err(Error::I2cError(mock::Error::Nack(0x28)))
+        ));
+        assert!(cap.is_interrupted().is_err());
     }
 }
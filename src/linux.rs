@@ -0,0 +1,281 @@
+//! Interrupt driven touch events on Linux
+//!
+//! [`TouchEventLoop`] waits for edges of the ALERT line, requested through the GPIO
+//! character device, and decodes the touch events of the device on each of them:
+//!
+//! ```ignore
+//! let mut chip = gpio_cdev::Chip::new("/dev/gpiochip0")?;
+//! let alert = cap1xxx::linux::request_alert_events(&mut cap, &chip.get_line(27)?)?;
+//! for event in TouchEventLoop::new(cap, alert)? {
+//!     println!("{:?}", event?);
+//! }
+//! ```
+use crate::error::Error;
+use crate::{RWResult, TouchDecoder, TouchEvent, TouchEvents, CAP1XXX};
+use core::fmt::{self, Debug, Display};
+use core::time::Duration;
+use embedded_hal::i2c::{ErrorType, I2c};
+use gpio_cdev::{EventRequestFlags, Line, LineEventHandle, LineRequestFlags};
+use std::sync::OnceLock;
+use std::time::Instant;
+
+/// Source of ALERT line events
+pub trait LineEventSource {
+    type Error;
+
+    /// Block until the next edge of the line and return its timestamp
+    ///
+    /// Timestamps must come from a monotonic clock, they time the holds and repeats
+    /// of [`TouchDecoder`].
+    fn wait_event(&mut self) -> Result<Duration, Self::Error>;
+}
+
+/// Time elapsed since the first call, from the monotonic clock
+fn monotonic_now() -> Duration {
+    static EPOCH: OnceLock<Instant> = OnceLock::new();
+    EPOCH.get_or_init(Instant::now).elapsed()
+}
+
+/// Edges are timestamped when read: before Linux 5.7 the timestamp of the kernel
+/// event comes from the realtime clock, which may jump.
+impl LineEventSource for LineEventHandle {
+    type Error = gpio_cdev::Error;
+
+    fn wait_event(&mut self) -> Result<Duration, Self::Error> {
+        self.get_event()?;
+        Ok(monotonic_now())
+    }
+}
+
+/// Request the edges of the ALERT line asserting the interrupt, following the polarity
+/// configured on the device: falling edges for an active low line (the power-on
+/// default), rising edges otherwise.
+pub fn request_alert_events<T>(
+    cap: &mut CAP1XXX<T>,
+    line: &Line,
+) -> Result<LineEventHandle, EventLoopError<<T as ErrorType>::Error, gpio_cdev::Error>>
+where
+    T: I2c,
+{
+    let edge = alert_edge(cap).map_err(EventLoopError::Device)?;
+    line.events(LineRequestFlags::INPUT, edge, "cap1xxx")
+        .map_err(EventLoopError::LineEvent)
+}
+
+fn alert_edge<T>(cap: &mut CAP1XXX<T>) -> RWResult<T, EventRequestFlags>
+where
+    T: I2c,
+{
+    Ok(if cap.is_alert_active_low()? {
+        EventRequestFlags::FALLING_EDGE
+    } else {
+        EventRequestFlags::RISING_EDGE
+    })
+}
+
+pub enum EventLoopError<E, S> {
+    /// Communication with the device failed
+    Device(Error<E>),
+    /// Requesting or waiting for the ALERT line failed
+    LineEvent(S),
+}
+
+impl<E: Display, S: Display> Display for EventLoopError<E, S> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            EventLoopError::Device(e) => write!(f, "{}", e),
+            EventLoopError::LineEvent(e) => write!(f, "ALERT line error: {}", e),
+        }
+    }
+}
+
+impl<E: Debug, S: Debug> Debug for EventLoopError<E, S> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            EventLoopError::Device(e) => write!(f, "Device({:?})", e),
+            EventLoopError::LineEvent(e) => write!(f, "LineEvent({:?})", e),
+        }
+    }
+}
+
+impl<E, S> std::error::Error for EventLoopError<E, S>
+where
+    E: std::error::Error,
+    S: std::error::Error,
+{
+}
+
+/// Blocking iterator of the touch events of a device, woken up by its ALERT line
+///
+/// Interrupts must be enabled for the inputs of interest; holds and repeats are
+/// reported when the device raises an interrupt for them.
+pub struct TouchEventLoop<T, S>
+where
+    T: I2c,
+{
+    cap: CAP1XXX<T>,
+    source: S,
+    decoder: TouchDecoder,
+    pending: Option<TouchEvents>,
+}
+
+impl<T, S> TouchEventLoop<T, S>
+where
+    T: I2c,
+    S: LineEventSource,
+{
+    /// Clears any pending interrupt so that the next touch produces an edge
+    pub fn new(mut cap: CAP1XXX<T>, source: S) -> RWResult<T, Self> {
+        let decoder = TouchDecoder::from_device(&mut cap)?;
+        cap.clear_interrupt()?;
+        Ok(Self {
+            cap,
+            source,
+            decoder,
+            pending: None,
+        })
+    }
+
+    pub fn device(&mut self) -> &mut CAP1XXX<T> {
+        &mut self.cap
+    }
+
+    pub fn release(self) -> (CAP1XXX<T>, S) {
+        (self.cap, self.source)
+    }
+}
+
+impl<T, S> Iterator for TouchEventLoop<T, S>
+where
+    T: I2c,
+    S: LineEventSource,
+{
+    type Item = Result<TouchEvent, EventLoopError<<T as ErrorType>::Error, S::Error>>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if let Some(event) = self.pending.as_mut().and_then(Iterator::next) {
+                return Some(Ok(event));
+            }
+            let now = match self.source.wait_event() {
+                Ok(now) => now,
+                Err(e) => return Some(Err(EventLoopError::LineEvent(e))),
+            };
+            match self.decoder.poll(&mut self.cap, now) {
+                Ok(events) => self.pending = Some(events),
+                Err(e) => return Some(Err(EventLoopError::Device(e))),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mock::Emulator;
+    use crate::TouchEventKind::*;
+    use std::collections::VecDeque;
+    use std::vec;
+    use std::vec::Vec;
+
+    enum Action {
+        Press(u8),
+        Release(u8),
+    }
+
+    // Touches the emulator and reports an edge at the given time, fails once exhausted
+    struct FakeLineEvents {
+        emulator: Emulator,
+        script: VecDeque<(u64, Action)>,
+    }
+
+    impl LineEventSource for FakeLineEvents {
+        type Error = &'static str;
+
+        fn wait_event(&mut self) -> Result<Duration, Self::Error> {
+            let (ms, action) = self.script.pop_front().ok_or("exhausted")?;
+            match action {
+                Action::Press(channel) => self.emulator.press(channel),
+                Action::Release(channel) => self.emulator.release(channel),
+            }
+            assert!(self.emulator.is_alert_asserted());
+            Ok(Duration::from_millis(ms))
+        }
+    }
+
+    fn event(channel: u8, kind: crate::TouchEventKind) -> TouchEvent {
+        TouchEvent { channel, kind }
+    }
+
+    #[test]
+    fn events_are_decoded_on_alert_edges() {
+        let emulator = Emulator::new();
+        let mut cap = CAP1XXX::new(emulator.clone(), 0x28, 6);
+        cap.init().unwrap();
+        emulator.press(5);
+        let source = FakeLineEvents {
+            emulator: emulator.clone(),
+            script: vec![
+                (10, Action::Press(0)),
+                (20, Action::Press(2)),
+                (400, Action::Release(0)),
+            ]
+            .into(),
+        };
+        let mut events = TouchEventLoop::new(cap, source).unwrap();
+        assert!(!emulator.is_alert_asserted());
+
+        let decoded: Vec<_> = events.by_ref().take(6).map(Result::unwrap).collect();
+        assert_eq!(
+            decoded,
+            vec![
+                event(0, Press),
+                event(5, Press),
+                event(2, Press),
+                event(0, Release),
+                event(2, Hold),
+                event(5, Hold),
+            ]
+        );
+        assert!(matches!(
+            events.next(),
+            Some(Err(EventLoopError::LineEvent("exhausted")))
+        ));
+    }
+
+    #[test]
+    fn alert_edge_follows_device_polarity() {
+        let mut cap = CAP1XXX::new(Emulator::new(), 0x28, 6);
+        cap.init().unwrap();
+        assert_eq!(
+            alert_edge(&mut cap).unwrap(),
+            EventRequestFlags::FALLING_EDGE
+        );
+        cap.set_alert_active_low(false).unwrap();
+        assert_eq!(
+            alert_edge(&mut cap).unwrap(),
+            EventRequestFlags::RISING_EDGE
+        );
+    }
+
+    #[test]
+    fn device_errors_are_reported() {
+        let emulator = Emulator::new().at_address(0x29);
+        let source = FakeLineEvents {
+            emulator: emulator.clone(),
+            script: vec![(0, Action::Press(0))].into(),
+        };
+        let mut events = TouchEventLoop {
+            cap: CAP1XXX::new(emulator, 0x28, 6),
+            source,
+            decoder: TouchDecoder::new(Duration::from_millis(210), Duration::from_millis(210), 0),
+            pending: None,
+        };
+        assert!(matches!(
+            events.next(),
+            Some(Err(EventLoopError::Device(Error::I2cError(
+                crate::mock::Error::Nack(0x28)
+            ))))
+        ));
+    }
+}
@@ -0,0 +1,620 @@
+//! In-memory cap1xxx register file emulator
+//!
+//! [`Emulator`] implements the embedded-hal I2C traits used by [`CAP1XXX`](crate::CAP1XXX)
+//! so that code built on top of the driver can be exercised without any hardware attached.
+//!
+//! The emulator is a cheap handle over a shared register file: clone it before handing it
+//! to the driver and keep the clone around to inject touches or inspect registers.
+//!
+//! ```
+//! use cap1xxx::mock::Emulator;
+//! use cap1xxx::CAP1XXX;
+//!
+//! let emulator = Emulator::new();
+//! let mut cap = CAP1XXX::new(emulator.clone(), 0x28, 6);
+//! cap.init().unwrap();
+//!
+//! emulator.press(2);
+//! assert!(cap.is_interrupted().unwrap());
+//! assert_eq!(cap.read_input_status().unwrap(), 0b100);
+//! ```
+use crate::consts::*;
+use crate::ChipModel;
+use alloc::rc::Rc;
+use core::cell::RefCell;
+use core::convert::Infallible;
+use core::fmt;
+use embedded_hal::digital::InputPin;
+use embedded_hal::i2c::{ErrorKind, ErrorType, I2c, NoAcknowledgeSource, Operation};
+
+/// Error returned by the emulator I2C bus
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Error {
+    /// No device answered at the given address
+    Nack(u8),
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Error::Nack(address) => write!(f, "no device at address {:#04x}", address),
+        }
+    }
+}
+
+impl embedded_hal::i2c::Error for Error {
+    fn kind(&self) -> ErrorKind {
+        match self {
+            Error::Nack(_) => ErrorKind::NoAcknowledge(NoAcknowledgeSource::Address),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for Error {}
+
+struct State {
+    address: u8,
+    registers: [u8; 256],
+    // register pointer, auto-incremented on each access
+    pointer: u8,
+    // channels currently touched
+    held: u8,
+    // channels whose analog calibration fails
+    calibration_failures: u8,
+    // acknowledged I2C transactions
+    transactions: usize,
+}
+
+impl State {
+    fn channel_count(&self) -> u8 {
+        ChipModel::from_product_id(self.registers[R_PRODUCT_ID as usize])
+            .map_or(8, ChipModel::channel_count)
+    }
+
+    fn write(&mut self, register: u8, value: u8) {
+        match register {
+            R_MAIN_CONTROL => {
+                let current = self.registers[register as usize];
+                // INT can only be cleared by the host, never set
+                let int = current & value & 1;
+                self.registers[register as usize] = (value & !1) | int;
+                if current & 1 == 1 && int == 0 {
+                    self.on_interrupt_cleared();
+                }
+            }
+            // BUT_LD_TH: input 1 threshold is loaded to all inputs
+            R_INPUT_1_THRESH if self.registers[R_RECALIBRATION as usize] & 0x80 != 0 => {
+                for register in R_INPUT_1_THRESH..=R_INPUT_8_THRESH {
+                    self.registers[register as usize] = value;
+                }
+            }
+            _ if is_writable(register) => self.registers[register as usize] = value,
+            _ => {}
+        }
+    }
+
+    fn read(&mut self, register: u8) -> u8 {
+        let value = self.registers[register as usize];
+        if register == R_CALIBRATION {
+            self.finish_calibration();
+        }
+        value
+    }
+
+    // Calibrations in progress complete once the host has seen them; the
+    // CALIBRATION_ACTIVATE bits of failed inputs stay set and ACAL_FAIL raises INT.
+    fn finish_calibration(&mut self) {
+        let failed = self.registers[R_CALIBRATION as usize] & self.calibration_failures;
+        self.registers[R_CALIBRATION as usize] = failed;
+        if failed != 0 {
+            self.registers[R_GENERAL_STATUS as usize] |= 0x20;
+            self.assert_interrupt();
+        }
+    }
+
+    // Latched status bits are released once the host clears INT, except
+    // for channels that are still being touched.
+    fn on_interrupt_cleared(&mut self) {
+        self.registers[R_INPUT_STATUS as usize] = self.held;
+        if self.held == 0 {
+            self.registers[R_GENERAL_STATUS as usize] &= !1;
+        }
+        if !self.pattern_detected() {
+            self.registers[R_GENERAL_STATUS as usize] &= !0b10;
+        }
+        if !self.touch_limit_exceeded() {
+            self.registers[R_GENERAL_STATUS as usize] &= !0b100;
+        }
+        if self.registers[R_CALIBRATION as usize] & self.calibration_failures == 0 {
+            self.registers[R_GENERAL_STATUS as usize] &= !0x20;
+        }
+    }
+
+    // Multiple touch blocking: more inputs touched than allowed by B_MULT_T. Only the
+    // MULT flag is emulated, the extra touches are still reported.
+    fn touch_limit_exceeded(&self) -> bool {
+        let config = self.registers[R_MTOUCH_CONFIG as usize];
+        let limit = u32::from((config >> 2) & 0b11) + 1;
+        config & 0x80 != 0 && self.held.count_ones() > limit
+    }
+
+    // Multiple-touch pattern detection, touches being always above the MTP threshold
+    fn pattern_detected(&self) -> bool {
+        let config = self.registers[R_MTOUCH_PAT_CONF as usize];
+        let pattern = self.registers[R_MTOUCH_PATTERN as usize];
+        if config & 0x80 == 0 || pattern == 0 {
+            false
+        } else if config & 0b10 != 0 {
+            self.held & pattern == pattern
+        } else {
+            self.held.count_ones() >= pattern.count_ones()
+        }
+    }
+
+    fn assert_interrupt(&mut self) {
+        self.registers[R_MAIN_CONTROL as usize] |= 1;
+    }
+
+    fn active_inputs(&self) -> u8 {
+        if self.registers[R_MAIN_CONTROL as usize] & 0x10 != 0 {
+            0
+        } else if self.registers[R_MAIN_CONTROL as usize] & 0x20 != 0 {
+            self.registers[R_STANDBY_CHANNEL as usize]
+        } else {
+            self.registers[R_INPUT_ENABLE as usize]
+        }
+    }
+
+    fn press(&mut self, channel: u8) {
+        let mask = 1 << channel;
+        if self.active_inputs() & mask == 0 {
+            return;
+        }
+        self.held |= mask;
+        self.registers[R_INPUT_STATUS as usize] |= mask;
+        self.registers[R_GENERAL_STATUS as usize] |= 1;
+        if self.registers[R_INTERRUPT_EN as usize] & mask != 0 {
+            self.assert_interrupt();
+        }
+        if self.touch_limit_exceeded() {
+            self.registers[R_GENERAL_STATUS as usize] |= 0b100;
+        }
+        if self.pattern_detected() {
+            self.registers[R_GENERAL_STATUS as usize] |= 0b10;
+            // MTP_ALERT
+            if self.registers[R_MTOUCH_PAT_CONF as usize] & 1 != 0 {
+                self.assert_interrupt();
+            }
+        }
+    }
+
+    fn release(&mut self, channel: u8) {
+        let mask = 1 << channel;
+        if self.held & mask == 0 {
+            return;
+        }
+        self.held &= !mask;
+        // INT_REL_n: 0 = interrupt on press and release
+        let int_on_release = self.registers[R_CONFIGURATION2 as usize] & 1 == 0;
+        if int_on_release && self.registers[R_INTERRUPT_EN as usize] & mask != 0 {
+            self.assert_interrupt();
+        }
+    }
+}
+
+/// Registers the host is allowed to modify
+fn is_writable(register: u8) -> bool {
+    matches!(
+        register,
+        R_MAIN_CONTROL
+        | R_SENSITIVITY..=R_SAMPLING_CONFIG
+        | R_CALIBRATION..=R_REPEAT_EN
+        | R_MTOUCH_CONFIG..=R_MTOUCH_PAT_CONF
+        | R_MTOUCH_PATTERN
+        | R_RECALIBRATION..=R_NOISE_THRESH
+        | R_STANDBY_CHANNEL..=R_CONFIGURATION2
+        | R_POWER_BUTTON
+        | R_POW_BUTTON_CONF
+        | R_LED_OUTPUT_TYPE..=R_LED_OUTPUT_CON
+        | R_LED_LTRANS_CON
+        | R_LED_MIRROR_CON
+        | R_LED_BEHAVIOUR_1
+        | R_LED_BEHAVIOUR_2
+        | R_LED_PULSE_1_PER..=R_LED_BREATHE_PER
+        | R_LED_CONFIG
+        | R_LED_PULSE_1_DUT..=R_LED_OFF_DELAY
+    )
+}
+
+/// Power-on register values, as documented in the datasheet
+fn power_on_registers(product_id: u8) -> [u8; 256] {
+    let mut registers = [0u8; 256];
+    let all_inputs = match ChipModel::from_product_id(product_id) {
+        Some(ChipModel::Cap1166) => 0b0011_1111,
+        _ => 0b1111_1111,
+    };
+    for &(register, value) in &[
+        (R_SENSITIVITY, 0x2F),
+        (R_GENERAL_CONFIG, 0x20),
+        (R_INPUT_ENABLE, all_inputs),
+        (R_INPUT_CONFIG, 0xA4),
+        (R_INPUT_CONFIG2, 0x07),
+        (R_SAMPLING_CONFIG, 0x39),
+        (R_INTERRUPT_EN, all_inputs),
+        (R_REPEAT_EN, all_inputs),
+        (R_MTOUCH_CONFIG, 0x80),
+        (R_MTOUCH_PATTERN, all_inputs),
+        (R_COUNT_O_LIMIT, 0x14),
+        (R_RECALIBRATION, 0x8A),
+        (R_NOISE_THRESH, 0x01),
+        (R_STANDBY_CONFIG, 0x39),
+        (R_STANDBY_SENS, 0x02),
+        (R_STANDBY_THRESH, 0x40),
+        (R_CONFIGURATION2, 0x40),
+        (R_POW_BUTTON_CONF, 0x22),
+        (R_LED_PULSE_1_PER, 0x20),
+        (R_LED_PULSE_2_PER, 0x14),
+        (R_LED_BREATHE_PER, 0x5D),
+        (R_LED_CONFIG, 0x04),
+        (R_LED_PULSE_1_DUT, 0xF0),
+        (R_LED_PULSE_2_DUT, 0xF0),
+        (R_LED_BREATHE_DUT, 0xF0),
+        (R_LED_DIRECT_DUT, 0xF0),
+        (R_PRODUCT_ID, product_id),
+        (R_MANUFACTURER_ID, MANUFACTURER_ID_MICROCHIP),
+        (R_REVISION, 0x83),
+    ] {
+        registers[register as usize] = value;
+    }
+    for register in R_INPUT_1_THRESH..=R_INPUT_8_THRESH {
+        registers[register as usize] = 0x40;
+    }
+    registers
+}
+
+/// Emulated cap1xxx device sitting on an I2C bus
+///
+/// Read-only registers ignore host writes, the INT bit of the main control register
+/// can only be cleared by the host and the input status bits stay latched until it is.
+/// Multiple touch blocking only sets the MULT flag: touches beyond the limit are still
+/// reported.
+#[derive(Clone)]
+pub struct Emulator {
+    state: Rc<RefCell<State>>,
+}
+
+impl Default for Emulator {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Emulator {
+    /// Emulate a cap1166 (as found on the Pimoroni GFX HAT) at the default address
+    pub fn new() -> Self {
+        Self::with_product_id(PID_CAP1166)
+    }
+
+    /// Emulate a device reporting the given product id at the default address
+    pub fn with_product_id(product_id: u8) -> Self {
+        Self {
+            state: Rc::new(RefCell::new(State {
+                address: DEFAULT_ADDR,
+                registers: power_on_registers(product_id),
+                pointer: 0,
+                held: 0,
+                calibration_failures: 0,
+                transactions: 0,
+            })),
+        }
+    }
+
+    /// Answer on another I2C address
+    pub fn at_address(self, address: u8) -> Self {
+        self.state.borrow_mut().address = address;
+        self
+    }
+
+    /// Number of sensor inputs of the emulated device
+    pub fn channel_count(&self) -> u8 {
+        self.state.borrow().channel_count()
+    }
+
+    /// Touch a sensor input
+    ///
+    /// Inputs that are disabled, or not enabled for standby while in standby, are
+    /// ignored, as are all inputs in deep sleep; an interrupt is raised if enabled for the input.
+    pub fn press(&self, channel: u8) {
+        assert!(channel < self.channel_count(), "invalid channel");
+        self.state.borrow_mut().press(channel);
+    }
+
+    /// Stop touching a sensor input
+    pub fn release(&self, channel: u8) {
+        assert!(channel < self.channel_count(), "invalid channel");
+        self.state.borrow_mut().release(channel);
+    }
+
+    /// Make the analog calibration of the given inputs fail
+    ///
+    /// Calibrations started by the host complete as soon as the host reads the
+    /// CALIBRATION_ACTIVATE register; those of the failing inputs set ACAL_FAIL and
+    /// assert the interrupt.
+    pub fn fail_calibration(&self, channels: u8) {
+        self.state.borrow_mut().calibration_failures = channels;
+    }
+
+    /// Bitmap of the inputs currently touched
+    pub fn held(&self) -> u8 {
+        self.state.borrow().held
+    }
+
+    /// Number of I2C transactions addressed to the device so far
+    pub fn transaction_count(&self) -> usize {
+        self.state.borrow().transactions
+    }
+
+    /// State of the ALERT line: asserted while the INT bit is set
+    pub fn is_alert_asserted(&self) -> bool {
+        self.register(R_MAIN_CONTROL) & 1 == 1
+    }
+
+    /// Input pin wired to the ALERT line
+    pub fn alert_pin(&self) -> AlertPin {
+        AlertPin(self.clone())
+    }
+
+    /// Current value of a register
+    pub fn register(&self, register: u8) -> u8 {
+        self.state.borrow().registers[register as usize]
+    }
+
+    /// Force the value of any register, including read-only ones
+    pub fn set_register(&self, register: u8, value: u8) {
+        self.state.borrow_mut().registers[register as usize] = value;
+    }
+
+    fn check_address(&self, address: u8) -> Result<(), Error> {
+        if self.state.borrow().address == address {
+            Ok(())
+        } else {
+            Err(Error::Nack(address))
+        }
+    }
+}
+
+/// ALERT line of an [`Emulator`], driven according to the configured alert polarity
+#[derive(Clone)]
+pub struct AlertPin(Emulator);
+
+impl AlertPin {
+    fn level(&self) -> bool {
+        let active_low = self.0.register(R_CONFIGURATION2) & 0x40 != 0;
+        self.0.is_alert_asserted() != active_low
+    }
+}
+
+impl embedded_hal::digital::ErrorType for AlertPin {
+    type Error = Infallible;
+}
+
+impl InputPin for AlertPin {
+    fn is_high(&mut self) -> Result<bool, Infallible> {
+        Ok(self.level())
+    }
+
+    fn is_low(&mut self) -> Result<bool, Infallible> {
+        Ok(!self.level())
+    }
+}
+
+#[cfg(feature = "async")]
+impl AlertPin {
+    // Resolves once the line is at the given level, polling it on every wake up
+    async fn wait_for_level(&self, high: bool) {
+        core::future::poll_fn(|cx| {
+            if self.level() == high {
+                core::task::Poll::Ready(())
+            } else {
+                cx.waker().wake_by_ref();
+                core::task::Poll::Pending
+            }
+        })
+        .await
+    }
+}
+
+#[cfg(feature = "async")]
+impl embedded_hal_async::digital::Wait for AlertPin {
+    async fn wait_for_high(&mut self) -> Result<(), Infallible> {
+        self.wait_for_level(true).await;
+        Ok(())
+    }
+
+    async fn wait_for_low(&mut self) -> Result<(), Infallible> {
+        self.wait_for_level(false).await;
+        Ok(())
+    }
+
+    async fn wait_for_rising_edge(&mut self) -> Result<(), Infallible> {
+        self.wait_for_level(false).await;
+        self.wait_for_level(true).await;
+        Ok(())
+    }
+
+    async fn wait_for_falling_edge(&mut self) -> Result<(), Infallible> {
+        self.wait_for_level(true).await;
+        self.wait_for_level(false).await;
+        Ok(())
+    }
+
+    async fn wait_for_any_edge(&mut self) -> Result<(), Infallible> {
+        let level = self.level();
+        self.wait_for_level(!level).await;
+        Ok(())
+    }
+}
+
+impl ErrorType for Emulator {
+    type Error = Error;
+}
+
+/// The first byte written in a transaction sets the register pointer, the following
+/// ones are written to consecutive registers. Reads start at the register pointer.
+impl I2c for Emulator {
+    fn transaction(
+        &mut self,
+        address: u8,
+        operations: &mut [Operation<'_>],
+    ) -> Result<(), Self::Error> {
+        self.check_address(address)?;
+        let mut state = self.state.borrow_mut();
+        state.transactions += 1;
+        let mut pointer_set = false;
+        for operation in operations {
+            match operation {
+                Operation::Write(bytes) => {
+                    for &byte in bytes.iter() {
+                        if pointer_set {
+                            let register = state.pointer;
+                            state.write(register, byte);
+                            state.pointer = register.wrapping_add(1);
+                        } else {
+                            state.pointer = byte;
+                            pointer_set = true;
+                        }
+                    }
+                }
+                Operation::Read(buffer) => {
+                    for byte in buffer.iter_mut() {
+                        let register = state.pointer;
+                        *byte = state.read(register);
+                        state.pointer = register.wrapping_add(1);
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(feature = "async")]
+impl embedded_hal_async::i2c::I2c for Emulator {
+    async fn transaction(
+        &mut self,
+        address: u8,
+        operations: &mut [Operation<'_>],
+    ) -> Result<(), Self::Error> {
+        I2c::transaction(self, address, operations)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn read_only_registers_ignore_writes() {
+        let mut emulator = Emulator::new();
+        emulator
+            .write(DEFAULT_ADDR, &[R_PRODUCT_ID, 0x00, 0x00])
+            .unwrap();
+        assert_eq!(emulator.register(R_PRODUCT_ID), PID_CAP1166);
+        assert_eq!(emulator.register(R_MANUFACTURER_ID), 0x5D);
+
+        emulator
+            .write(DEFAULT_ADDR, &[R_INPUT_STATUS, 0xFF])
+            .unwrap();
+        assert_eq!(emulator.register(R_INPUT_STATUS), 0);
+
+        // BASE_COUNT_OUT_OF_LIMIT, between writable registers of a block
+        emulator
+            .write(DEFAULT_ADDR, &[R_MTOUCH_PATTERN, 0x01, 0xFF, 0x0A])
+            .unwrap();
+        assert_eq!(emulator.register(R_MTOUCH_PATTERN), 0x01);
+        assert_eq!(emulator.register(R_COUNT_O_LIMIT), 0x14);
+        assert_eq!(emulator.register(R_RECALIBRATION), 0x0A);
+    }
+
+    #[test]
+    fn block_access_auto_increments() {
+        let mut emulator = Emulator::new();
+        emulator
+            .write(DEFAULT_ADDR, &[R_LED_PULSE_1_DUT, 1, 2, 3])
+            .unwrap();
+        let mut buf = [0u8; 4];
+        emulator
+            .write_read(DEFAULT_ADDR, &[R_LED_PULSE_1_DUT], &mut buf)
+            .unwrap();
+        assert_eq!(buf, [1, 2, 3, 0xF0]);
+
+        // the register pointer is kept between transactions
+        emulator.write(DEFAULT_ADDR, &[R_LED_PULSE_2_DUT]).unwrap();
+        emulator.read(DEFAULT_ADDR, &mut buf[..2]).unwrap();
+        assert_eq!(buf[..2], [2, 3]);
+        emulator.read(DEFAULT_ADDR, &mut buf[..2]).unwrap();
+        assert_eq!(buf[..2], [0xF0, 0]);
+    }
+
+    #[test]
+    fn linked_thresholds() {
+        let mut emulator = Emulator::new();
+        emulator
+            .write(DEFAULT_ADDR, &[R_INPUT_1_THRESH, 0x10])
+            .unwrap();
+        assert_eq!(emulator.register(R_INPUT_6_THRESH), 0x10);
+
+        emulator
+            .write(DEFAULT_ADDR, &[R_RECALIBRATION, 0x0A])
+            .unwrap();
+        emulator
+            .write(DEFAULT_ADDR, &[R_INPUT_1_THRESH, 0x20])
+            .unwrap();
+        assert_eq!(emulator.register(R_INPUT_1_THRESH), 0x20);
+        assert_eq!(emulator.register(R_INPUT_6_THRESH), 0x10);
+    }
+
+    #[test]
+    fn wrong_address_is_nacked() {
+        let mut emulator = Emulator::new();
+        assert_eq!(
+            emulator.write(0x2C, &[R_MAIN_CONTROL, 0]),
+            Err(Error::Nack(0x2C))
+        );
+    }
+
+    #[test]
+    fn status_is_latched_until_interrupt_cleared() {
+        let mut emulator = Emulator::new();
+        emulator.press(1);
+        emulator.press(3);
+        emulator.release(3);
+        assert!(emulator.is_alert_asserted());
+        assert_eq!(emulator.register(R_INPUT_STATUS), 0b1010);
+
+        // setting INT from the host is not possible, clearing it is
+        emulator.write(DEFAULT_ADDR, &[R_MAIN_CONTROL, 0]).unwrap();
+        assert!(!emulator.is_alert_asserted());
+        assert_eq!(emulator.register(R_INPUT_STATUS), 0b0010);
+        emulator.write(DEFAULT_ADDR, &[R_MAIN_CONTROL, 1]).unwrap();
+        assert!(!emulator.is_alert_asserted());
+    }
+
+    #[test]
+    fn disabled_inputs_do_not_interrupt() {
+        let mut emulator = Emulator::new();
+        emulator
+            .write(DEFAULT_ADDR, &[R_INTERRUPT_EN, 0b0000_0001])
+            .unwrap();
+        emulator.press(1);
+        assert!(!emulator.is_alert_asserted());
+        assert_eq!(emulator.register(R_INPUT_STATUS), 0b10);
+
+        emulator
+            .write(DEFAULT_ADDR, &[R_INPUT_ENABLE, 0b0000_0001])
+            .unwrap();
+        emulator.press(2);
+        assert_eq!(emulator.held(), 0b10);
+    }
+}
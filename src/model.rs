@@ -0,0 +1,57 @@
+use crate::consts::*;
+
+/// Supported chip variants, as reported by the PRODUCT_ID register
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ChipModel {
+    /// 8 inputs, no LED driver
+    Cap1208,
+    /// 8 inputs, 8 LED drivers
+    Cap1188,
+    /// 6 inputs, 6 LED drivers (Pimoroni GFX HAT)
+    Cap1166,
+}
+
+impl ChipModel {
+    /// Model matching a PRODUCT_ID register value, if supported
+    pub fn from_product_id(product_id: u8) -> Option<Self> {
+        match product_id {
+            PID_CAP1208 => Some(ChipModel::Cap1208),
+            PID_CAP1188 => Some(ChipModel::Cap1188),
+            PID_CAP1166 => Some(ChipModel::Cap1166),
+            _ => None,
+        }
+    }
+
+    pub fn product_id(self) -> u8 {
+        match self {
+            ChipModel::Cap1208 => PID_CAP1208,
+            ChipModel::Cap1188 => PID_CAP1188,
+            ChipModel::Cap1166 => PID_CAP1166,
+        }
+    }
+
+    /// Number of capacitive sensor inputs
+    pub fn channel_count(self) -> u8 {
+        match self {
+            ChipModel::Cap1208 | ChipModel::Cap1188 => 8,
+            ChipModel::Cap1166 => 6,
+        }
+    }
+
+    /// Number of LED outputs
+    pub fn led_count(self) -> u8 {
+        match self {
+            ChipModel::Cap1208 => 0,
+            ChipModel::Cap1188 => 8,
+            ChipModel::Cap1166 => 6,
+        }
+    }
+}
+
+/// Identity of a device, as read by `probe`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DeviceInfo {
+    pub model: ChipModel,
+    /// Silicon revision, from the REVISION register
+    pub revision: u8,
+}
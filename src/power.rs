@@ -0,0 +1,103 @@
+use crate::{RWResult, CAP1XXX};
+use core::ops::{Deref, DerefMut};
+use embedded_hal::i2c::I2c;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PowerState {
+    /// All enabled inputs are sampled
+    Active,
+    /// Only the inputs enabled for standby are sampled
+    Standby,
+    /// Nothing is sampled, the device waits for the WAKE pin or the host
+    DeepSleep,
+}
+
+impl PowerState {
+    /// Decode the power state from the main control register
+    pub fn from_main_control(value: u8) -> Self {
+        if value & 0x10 != 0 {
+            PowerState::DeepSleep
+        } else if value & 0x20 != 0 {
+            PowerState::Standby
+        } else {
+            PowerState::Active
+        }
+    }
+}
+
+/// Keeps the device in deep sleep until dropped
+///
+/// Created by [`CAP1XXX::deep_sleep`]. When dropped, or explicitly woken with
+/// [`wake`](Self::wake), the device leaves deep sleep and the input and interrupt
+/// enables saved when entering deep sleep are written back. The driver stays usable
+/// through the guard in the meantime.
+pub struct DeepSleep<'a, T>
+where
+    T: I2c,
+{
+    cap: &'a mut CAP1XXX<T>,
+    inputs: u8,
+    interrupts: u8,
+    awake: bool,
+}
+
+impl<'a, T> DeepSleep<'a, T>
+where
+    T: I2c,
+{
+    pub(crate) fn enter(cap: &'a mut CAP1XXX<T>) -> RWResult<T, Self> {
+        let inputs = cap.get_inputs_enabled()?;
+        let interrupts = cap.get_interrupts_enabled()?;
+        cap.enter_deep_sleep()?;
+        Ok(Self {
+            cap,
+            inputs,
+            interrupts,
+            awake: false,
+        })
+    }
+
+    /// Leave deep sleep and restore the input configuration, reporting errors
+    pub fn wake(mut self) -> RWResult<T, ()> {
+        self.restore()
+    }
+
+    fn restore(&mut self) -> RWResult<T, ()> {
+        self.awake = true;
+        self.cap.exit_deep_sleep()?;
+        self.cap.enable_inputs(self.inputs)?;
+        self.cap.enable_interrupts(self.interrupts)
+    }
+}
+
+impl<'a, T> Deref for DeepSleep<'a, T>
+where
+    T: I2c,
+{
+    type Target = CAP1XXX<T>;
+
+    fn deref(&self) -> &CAP1XXX<T> {
+        self.cap
+    }
+}
+
+impl<'a, T> DerefMut for DeepSleep<'a, T>
+where
+    T: I2c,
+{
+    fn deref_mut(&mut self) -> &mut CAP1XXX<T> {
+        self.cap
+    }
+}
+
+impl<'a, T> Drop for DeepSleep<'a, T>
+where
+    T: I2c,
+{
+    fn drop(&mut self) {
+        if !self.awake {
+            // errors can only be reported through wake()
+            let _ = self.restore();
+        }
+    }
+}
@@ -0,0 +1,327 @@
+//! Device profiles, loaded from any serde format
+//!
+//! A [`Profile`] gathers the touch and LED settings of a device so they can be kept in a
+//! TOML or JSON file and applied without recompiling. Every setting is optional, settings
+//! missing from the profile are left untouched on the device.
+//!
+//! ```
+//! use cap1xxx::profile::Profile;
+//! use cap1xxx::{RWResult, CAP1XXX};
+//! use embedded_hal::i2c::I2c;
+//!
+//! let profile: Profile = toml::from_str(
+//!     r#"
+//!     sensitivity = "X16"
+//!     thresholds = [40, 40, 48, 48, 64, 64]
+//!     led_behaviours = ["Breathe", "Direct", "Pulse1"]
+//!     breathe_period_ms = 1500
+//!
+//!     [duties.breathe]
+//!     min = 0
+//!     max = 12
+//!     "#,
+//! )
+//! .unwrap();
+//!
+//! fn configure<T: I2c>(cap: &mut CAP1XXX<T>, profile: &Profile) -> RWResult<T, ()> {
+//!     profile.apply(cap)
+//! }
+//! ```
+use crate::config::*;
+use crate::error::Error;
+use crate::registers;
+use crate::{RWResult, CAP1XXX};
+use alloc::vec::Vec;
+use core::time::Duration;
+use embedded_hal::i2c::I2c;
+use serde::{Deserialize, Serialize};
+
+/// Rise and fall times of the direct LED behaviour
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct DirectRamp {
+    /// 0 to 2000ms, rounded down to a multiple of 250ms
+    pub rise_ms: u16,
+    /// 0 to 2000ms, rounded down to a multiple of 250ms
+    pub fall_ms: u16,
+}
+
+/// Touch and LED settings of a device
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Profile {
+    /// Bitmap of the enabled inputs
+    pub inputs: Option<u8>,
+    /// Bitmap of the inputs raising interrupts
+    pub interrupts: Option<u8>,
+    /// Bitmap of the inputs repeating interrupts when held
+    pub repeat: Option<u8>,
+    pub multitouch: Option<bool>,
+    pub touch_limit: Option<TouchLimit>,
+    pub multiple_touch_pattern: Option<MultipleTouchPattern>,
+    /// 35 to 560ms
+    pub hold_delay_ms: Option<u16>,
+    /// 35 to 560ms
+    pub repeat_rate_ms: Option<u16>,
+    pub sampling: Option<SamplingConfig>,
+    pub sensitivity: Option<Sensitivity>,
+    pub base_shift: Option<BaseShift>,
+    pub noise_threshold: Option<NoiseThreshold>,
+    /// Touch thresholds (0 to 127) of the first inputs
+    pub thresholds: Option<Vec<u8>>,
+    pub standby: Option<StandbyConfig>,
+    /// Behaviours of the first LEDs
+    pub led_behaviours: Option<Vec<LedBehaviour>>,
+    /// 32 to 4064ms
+    pub pulse1_period_ms: Option<u16>,
+    /// 32 to 4064ms
+    pub pulse2_period_ms: Option<u16>,
+    /// 32 to 4064ms
+    pub breathe_period_ms: Option<u16>,
+    /// Number of pulses, 1 to 8
+    pub pulse1_count: Option<u8>,
+    /// Number of pulses, 1 to 8
+    pub pulse2_count: Option<u8>,
+    pub duties: Option<LedDuties>,
+    pub direct_ramp: Option<DirectRamp>,
+}
+
+fn ms(ms: u16) -> Duration {
+    Duration::from_millis(u64::from(ms))
+}
+
+impl Profile {
+    /// Write the settings of the profile to the device
+    ///
+    /// Fails before writing anything if the profile has more thresholds or LED behaviours
+    /// than the device has inputs or LEDs, or a pulse count outside of 1 to 8.
+    pub fn apply<T>(&self, cap: &mut CAP1XXX<T>) -> RWResult<T, ()>
+    where
+        T: I2c,
+    {
+        if let Some(thresholds) = &self.thresholds {
+            if thresholds.len() > cap.number_of_channels() as usize {
+                return Err(Error::ChannelNumberOverflowError);
+            }
+        }
+        if let Some(behaviours) = &self.led_behaviours {
+            if behaviours.len() > cap.number_of_leds() as usize {
+                return Err(Error::LedNumberOverflowError);
+            }
+        }
+        for count in [self.pulse1_count, self.pulse2_count].iter().flatten() {
+            registers::led_pulse_count(0, *count)?;
+        }
+        if let Some(inputs) = self.inputs {
+            cap.enable_inputs(inputs)?;
+        }
+        if let Some(interrupts) = self.interrupts {
+            cap.enable_interrupts(interrupts)?;
+        }
+        if let Some(repeat) = self.repeat {
+            cap.enable_repeat(repeat)?;
+        }
+        if let Some(multitouch) = self.multitouch {
+            cap.enable_multitouch(multitouch)?;
+        }
+        if let Some(limit) = self.touch_limit {
+            cap.set_touch_limit(limit)?;
+        }
+        if let Some(pattern) = &self.multiple_touch_pattern {
+            cap.configure_multiple_touch_pattern(pattern)?;
+        }
+        if let Some(delay) = self.hold_delay_ms {
+            cap.set_hold_delay(ms(delay))?;
+        }
+        if let Some(rate) = self.repeat_rate_ms {
+            cap.set_repeat_rate(ms(rate))?;
+        }
+        if let Some(sampling) = &self.sampling {
+            cap.set_sampling_config(sampling)?;
+        }
+        if let Some(sensitivity) = self.sensitivity {
+            cap.set_sensitivity(sensitivity)?;
+        }
+        if let Some(base_shift) = self.base_shift {
+            cap.set_base_shift(base_shift)?;
+        }
+        if let Some(threshold) = self.noise_threshold {
+            cap.set_noise_threshold(threshold)?;
+        }
+        if let Some(thresholds) = &self.thresholds {
+            cap.set_thresholds(thresholds)?;
+        }
+        if let Some(standby) = &self.standby {
+            cap.configure_standby(standby)?;
+        }
+        if let Some(behaviours) = &self.led_behaviours {
+            for (led, behaviour) in behaviours.iter().enumerate() {
+                cap.set_led_behaviour(led as u8, behaviour.bits())?;
+            }
+        }
+        if let Some(period) = self.pulse1_period_ms {
+            cap.set_led_pulse1_period(ms(period))?;
+        }
+        if let Some(period) = self.pulse2_period_ms {
+            cap.set_led_pulse2_period(ms(period))?;
+        }
+        if let Some(period) = self.breathe_period_ms {
+            cap.set_led_breathe_period(ms(period))?;
+        }
+        if let Some(count) = self.pulse1_count {
+            cap.set_led_pulse1_count(count)?;
+        }
+        if let Some(count) = self.pulse2_count {
+            cap.set_led_pulse2_count(count)?;
+        }
+        if let Some(duties) = &self.duties {
+            cap.set_led_duties(duties)?;
+        }
+        if let Some(ramp) = self.direct_ramp {
+            cap.set_led_direct_ramp_rate(ramp.rise_ms, ramp.fall_ms)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::consts::*;
+    use crate::mock::Emulator;
+    use std::vec;
+
+    const TOML: &str = r#"
+        inputs = 0b00111111
+        repeat = 0b00000011
+        hold_delay_ms = 350
+        sensitivity = "X16"
+        base_shift = "X256"
+        noise_threshold = "Percent50"
+        thresholds = [10, 20, 30]
+        led_behaviours = ["Breathe", "Pulse2"]
+        breathe_period_ms = 1024
+        pulse2_count = 3
+
+        [sampling]
+        averaging = "Samples8"
+        sample_time = "Ms2_56"
+        cycle_time = "Ms70"
+
+        [duties]
+        pulse1 = { min = 0, max = 15 }
+        pulse2 = { min = 1, max = 14 }
+        breathe = { min = 2, max = 13 }
+        direct = { min = 3, max = 12 }
+
+        [direct_ramp]
+        rise_ms = 500
+        fall_ms = 1000
+    "#;
+
+    #[test]
+    fn apply_toml_profile() {
+        let profile: Profile = toml::from_str(TOML).unwrap();
+        let emulator = Emulator::new();
+        let mut cap = CAP1XXX::new(emulator.clone(), 0x28, 6);
+        cap.init().unwrap();
+        cap.link_thresholds(false).unwrap();
+        profile.apply(&mut cap).unwrap();
+
+        assert_eq!(cap.get_inputs_enabled().unwrap(), 0b00111111);
+        assert_eq!(cap.get_repeat_enabled().unwrap(), 0b11);
+        assert_eq!(cap.get_interrupts_enabled().unwrap(), 0xFF);
+        assert_eq!(cap.get_hold_delay().unwrap(), Duration::from_millis(350));
+        assert_eq!(cap.get_sensitivity().unwrap(), Sensitivity::X16);
+        assert_eq!(cap.get_base_shift().unwrap(), BaseShift::X256);
+        assert_eq!(
+            cap.get_noise_threshold().unwrap(),
+            NoiseThreshold::Percent50
+        );
+        assert_eq!(
+            cap.get_sampling_config().unwrap(),
+            SamplingConfig::from_register(0b0011_1101)
+        );
+        assert_eq!(
+            cap.read_threshold_values().unwrap()[..],
+            [10, 20, 30, 0x40, 0x40, 0x40]
+        );
+        assert_eq!(emulator.register(R_LED_BEHAVIOUR_1), 0b1011);
+        assert_eq!(emulator.register(R_LED_BREATHE_PER) & 0x7F, 32);
+        assert_eq!((emulator.register(R_LED_CONFIG) >> 3) & 0b111, 2);
+        assert_eq!(
+            cap.get_led_duties().unwrap(),
+            LedDuties::from_registers([0xF0, 0xE1, 0xD2, 0xC3])
+        );
+        assert_eq!(emulator.register(R_LED_DIRECT_RAMP), 0x24);
+    }
+
+    #[test]
+    fn json_round_trip() {
+        let profile = Profile {
+            sensitivity: Some(Sensitivity::X4),
+            thresholds: Some(vec![1, 2]),
+            led_behaviours: Some(vec![LedBehaviour::Pulse1]),
+            standby: Some(StandbyConfig::default()),
+            ..Profile::default()
+        };
+        let json = serde_json::to_string(&profile).unwrap();
+        assert_eq!(serde_json::from_str::<Profile>(&json).unwrap(), profile);
+        assert_eq!(
+            serde_json::from_str::<Profile>("{}").unwrap(),
+            Profile::default()
+        );
+        assert!(serde_json::from_str::<Profile>(r#"{"sensitivty": "X4"}"#).is_err());
+    }
+
+    #[test]
+    fn profile_larger_than_device() {
+        let emulator = Emulator::new();
+        let mut cap = CAP1XXX::new(emulator.clone(), 0x28, 6);
+        let profile = Profile {
+            inputs: Some(0b1),
+            sensitivity: Some(Sensitivity::X1),
+            led_behaviours: Some(vec![LedBehaviour::Breathe; 8]),
+            ..Profile::default()
+        };
+        assert!(matches!(
+            profile.apply(&mut cap),
+            Err(Error::LedNumberOverflowError)
+        ));
+        let profile = Profile {
+            thresholds: Some(vec![0x10; 7]),
+            ..profile
+        };
+        assert!(matches!(
+            profile.apply(&mut cap),
+            Err(Error::ChannelNumberOverflowError)
+        ));
+        // nothing written
+        assert_eq!(emulator.transaction_count(), 0);
+    }
+
+    #[test]
+    fn pulse_counts_out_of_range() {
+        let emulator = Emulator::new();
+        let mut cap = CAP1XXX::new(emulator.clone(), 0x28, 6);
+        for &count in &[0, 9] {
+            let profile = Profile {
+                inputs: Some(0b1),
+                pulse2_count: Some(count),
+                ..Profile::default()
+            };
+            assert!(matches!(
+                profile.apply(&mut cap),
+                Err(Error::PulseCountError(c)) if c == count
+            ));
+        }
+        assert_eq!(emulator.transaction_count(), 0);
+        let profile = Profile {
+            pulse1_count: Some(8),
+            pulse2_count: Some(1),
+            ..Profile::default()
+        };
+        profile.apply(&mut cap).unwrap();
+        assert_eq!(emulator.register(R_LED_CONFIG) & 0b11_1111, 0b111);
+    }
+}
@@ -0,0 +1,475 @@
+//! Register level encoding of the driver operations
+//!
+//! Shared by the blocking and async drivers, which only differ in the way
+//! the resulting register accesses are sent on the bus.
+use crate::channels::ChannelValues;
+use crate::config::{
+    BaseShift, Config, MultipleTouchPattern, NoiseThreshold, Sensitivity, TouchLimit,
+};
+use crate::consts::*;
+use crate::error::Error;
+use crate::model::{ChipModel, DeviceInfo};
+use crate::status::{GeneralStatus, InputDiagnostics};
+use core::cmp::{max, min};
+use core::time::Duration;
+
+/// A change of a single register
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Access {
+    /// Overwrite the register
+    Write { register: u8, value: u8 },
+    /// Read the register and only change the bits selected by the mask
+    Modify { register: u8, mask: u8, value: u8 },
+}
+
+impl Access {
+    pub fn write(register: u8, value: u8) -> Self {
+        Access::Write { register, value }
+    }
+
+    pub fn bit(register: u8, bit: u8, state: bool) -> Self {
+        Access::Modify {
+            register,
+            mask: 1 << bit,
+            value: (state as u8) << bit,
+        }
+    }
+
+    pub fn bits(register: u8, offset: u8, size: u8, bits: u8) -> Self {
+        let mask = (((1u16 << size) - 1) as u8) << offset;
+        Access::Modify {
+            register,
+            mask,
+            value: (bits << offset) & mask,
+        }
+    }
+
+    /// New value of a modified register
+    pub fn modified(mask: u8, value: u8, current: u8) -> u8 {
+        (current & !mask) | value
+    }
+}
+
+/// Longest block of registers written in a single transaction
+pub(crate) const MAX_BLOCK_LEN: usize = 8;
+
+/// Register address followed by the values of consecutive registers, sent as a single
+/// write so that buses issuing a repeated start between operations are supported.
+pub(crate) struct Block {
+    buffer: [u8; MAX_BLOCK_LEN + 1],
+    len: usize,
+}
+
+impl Block {
+    /// Panics if there are more than `MAX_BLOCK_LEN` values
+    pub fn new(register: u8, values: &[u8]) -> Self {
+        assert!(values.len() <= MAX_BLOCK_LEN, "register block too long");
+        let mut buffer = [0u8; MAX_BLOCK_LEN + 1];
+        buffer[0] = register;
+        buffer[1..=values.len()].copy_from_slice(values);
+        Self {
+            buffer,
+            len: values.len() + 1,
+        }
+    }
+
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.buffer[..self.len]
+    }
+}
+
+pub(crate) fn duration_to_rate_scale(duration: Duration) -> u8 {
+    let ms = duration.as_millis();
+    let ms = max(35, ms);
+    let ms = min(560, ms);
+    ((ms - ms % 35 - 35) / 35) as u8
+}
+
+pub(crate) fn rate_scale_to_duration(scale: u8) -> Duration {
+    Duration::from_millis(35 * (u64::from(scale & 0b1111) + 1))
+}
+
+pub(crate) fn duration_to_period_value(period: Duration) -> u8 {
+    ((min(4064, period.as_millis()) / 32) & 127) as u8
+}
+
+/// Start and length of the burst read of the PRODUCT_ID, MANUFACTURER_ID and REVISION
+/// registers by `probe`
+pub(crate) const ID_START: u8 = R_PRODUCT_ID;
+pub(crate) const ID_LEN: usize = (R_REVISION - R_PRODUCT_ID) as usize + 1;
+
+/// Identify the device, failing if it is not a supported Microchip part
+pub(crate) fn device_info<E>(registers: &[u8; ID_LEN]) -> Result<DeviceInfo, Error<E>> {
+    let [product_id, manufacturer_id, revision] = *registers;
+    if manufacturer_id != MANUFACTURER_ID_MICROCHIP {
+        return Err(Error::UnknownManufacturerError(manufacturer_id));
+    }
+    let model =
+        ChipModel::from_product_id(product_id).ok_or(Error::UnsupportedProductError(product_id))?;
+    Ok(DeviceInfo { model, revision })
+}
+
+/// Registers written by `init_with`, in order
+pub(crate) fn init_sequence(config: &Config) -> [Access; 11] {
+    [
+        Access::write(R_INPUT_ENABLE, config.inputs),
+        Access::write(R_INTERRUPT_EN, config.interrupts),
+        Access::write(R_REPEAT_EN, config.repeat),
+        multitouch(config.multitouch),
+        hold_delay(config.hold_delay),
+        repeat_rate(config.repeat_rate),
+        Access::write(R_SAMPLING_CONFIG, config.sampling.to_register()),
+        // recalibrate
+        Access::write(R_CALIBRATION, config.calibration),
+        Access::write(
+            R_SENSITIVITY,
+            config.sensitivity.bits() << 4 | config.base_shift.bits(),
+        ),
+        Access::write(R_GENERAL_CONFIG, config.general_config),
+        Access::write(R_CONFIGURATION2, config.configuration2),
+    ]
+}
+
+/// Clear the interrupt flag, bit 0, of the main control register
+pub(crate) fn clear_interrupt() -> Access {
+    Access::bit(R_MAIN_CONTROL, 0, false)
+}
+
+/// Interrupt flag, bit 0 of the main control register
+pub(crate) fn interrupted(main_control: u8) -> bool {
+    main_control & 1 != 0
+}
+
+/// STBY, bit 5 of the main control register
+pub(crate) fn standby(enable: bool) -> Access {
+    Access::bit(R_MAIN_CONTROL, 5, enable)
+}
+
+pub(crate) fn in_standby(main_control: u8) -> bool {
+    main_control & 0x20 != 0
+}
+
+/// DSLEEP, bit 4 of the main control register
+pub(crate) fn deep_sleep(enable: bool) -> Access {
+    Access::bit(R_MAIN_CONTROL, 4, enable)
+}
+
+/// GAIN, bits 6 and 7 of the main control register
+pub(crate) fn gain(bits: u8) -> Access {
+    Access::bits(R_MAIN_CONTROL, 6, 2, bits)
+}
+
+/// MAX_DUR_EN, bit 3 of the general configuration register: recalibrate inputs held too long
+pub(crate) fn auto_recalibrate(enable: bool) -> Access {
+    Access::bit(R_GENERAL_CONFIG, 3, enable)
+}
+
+/// DIS_ANA_NOISE, bit 4 of the general configuration register
+pub(crate) fn analog_noise_filter(enable: bool) -> Access {
+    Access::bit(R_GENERAL_CONFIG, 4, !enable)
+}
+
+/// DIS_DIG_NOISE, bit 5 of the general configuration register
+pub(crate) fn digital_noise_filter(enable: bool) -> Access {
+    Access::bit(R_GENERAL_CONFIG, 5, !enable)
+}
+
+/// Start the calibration of the given inputs, ignoring the bits of missing inputs
+///
+/// The CALIBRATION_ACTIVATE bits are cleared by the device once each input is calibrated.
+pub(crate) fn calibrate(channels: u8, channel_count: u8) -> Access {
+    let mask = ((1u16 << channel_count.min(8)) - 1) as u8;
+    Access::write(R_CALIBRATION, channels & mask)
+}
+
+/// Interval between two polls of `wait_calibration_done`
+pub(crate) const CALIBRATION_POLL: Duration = Duration::from_millis(10);
+
+/// Outcome of a calibration, from the CALIBRATION_ACTIVATE bits still set (`pending`),
+/// the general status and the BASE_COUNT_OUT_OF_LIMIT register
+///
+/// Returns the bitmap of the failed inputs once the calibration is over: the inputs still
+/// calibrating when ACAL_FAIL is raised and, with BC_OUT, the inputs whose base count is
+/// out of limit. `None` while the calibration is running.
+pub(crate) fn calibration_failures(
+    channels: u8,
+    pending: u8,
+    general: &GeneralStatus,
+    out_of_limit: u8,
+) -> Option<u8> {
+    let pending = pending & channels;
+    if pending != 0 && !general.calibration_failed {
+        return None;
+    }
+    let mut failed = pending;
+    if general.base_count_out_of_limit {
+        failed |= out_of_limit & channels;
+    }
+    Some(failed)
+}
+
+/// Next step of `wait_calibration_done`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum CalibrationStep {
+    /// The calibration is over, with the bitmap of the failed inputs
+    Done(u8),
+    /// The calibration is running, poll again after this number of milliseconds
+    Wait(u32),
+}
+
+/// Polling state of `wait_calibration_done`
+///
+/// Each poll reads the general status first, so that inputs completing in between are
+/// not reported as failed, then the CALIBRATION_ACTIVATE bits and, with BC_OUT only,
+/// the BASE_COUNT_OUT_OF_LIMIT register.
+pub(crate) struct CalibrationWait {
+    channels: u8,
+    timeout: Duration,
+    waited: Duration,
+}
+
+impl CalibrationWait {
+    pub fn new(channels: u8, timeout: Duration) -> Self {
+        Self {
+            channels,
+            timeout,
+            waited: Duration::ZERO,
+        }
+    }
+
+    /// Fails with `Error::CalibrationTimeout` once the calibration ran for `timeout`
+    pub fn step<E>(
+        &mut self,
+        general: &GeneralStatus,
+        pending: u8,
+        out_of_limit: u8,
+    ) -> Result<CalibrationStep, Error<E>> {
+        if let Some(failed) = calibration_failures(self.channels, pending, general, out_of_limit) {
+            return Ok(CalibrationStep::Done(failed));
+        }
+        if self.waited >= self.timeout {
+            return Err(Error::CalibrationTimeout);
+        }
+        self.waited += CALIBRATION_POLL;
+        Ok(CalibrationStep::Wait(CALIBRATION_POLL.as_millis() as u32))
+    }
+}
+
+/// Configure the multiple-touch pattern detector, pattern first so that it is
+/// complete when the detector gets enabled
+pub(crate) fn multiple_touch_pattern(pattern: &MultipleTouchPattern) -> [Access; 2] {
+    let [config, inputs] = pattern.to_registers();
+    [
+        Access::write(R_MTOUCH_PATTERN, inputs),
+        Access::write(R_MTOUCH_PAT_CONF, config),
+    ]
+}
+
+/// B_MULT_T bits of the multiple touch configuration register
+pub(crate) fn touch_limit(limit: TouchLimit) -> Access {
+    Access::bits(R_MTOUCH_CONFIG, 2, 2, limit.bits())
+}
+
+/// Touch limit from the multiple touch configuration register
+pub(crate) fn touch_limit_from(mtouch_config: u8) -> TouchLimit {
+    TouchLimit::from_bits(mtouch_config >> 2)
+}
+
+/// Toggles multi-touch by toggling the multi-touch block bit in the config register
+pub(crate) fn multitouch(enable: bool) -> Access {
+    Access::bit(R_MTOUCH_CONFIG, 7, !enable)
+}
+
+/// Alert polarity from the CONFIGURATION2 register, bit 6 (ALT_POL)
+pub(crate) fn alert_active_low(configuration2: u8) -> bool {
+    configuration2 & 0x40 != 0
+}
+
+pub(crate) fn alert_polarity(active_low: bool) -> Access {
+    Access::bit(R_CONFIGURATION2, 6, active_low)
+}
+
+pub(crate) fn hold_delay(delay: Duration) -> Access {
+    Access::bits(R_INPUT_CONFIG2, 0, 4, duration_to_rate_scale(delay))
+}
+
+pub(crate) fn repeat_rate(delay: Duration) -> Access {
+    Access::bits(R_INPUT_CONFIG, 0, 4, duration_to_rate_scale(delay))
+}
+
+pub(crate) fn threshold<E>(channel: u8, threshold: u8, channels: u8) -> Result<Access, Error<E>> {
+    if channel >= channels {
+        Err(Error::ChannelNumberOverflowError)
+    } else {
+        Ok(Access::write(
+            R_INPUT_1_THRESH + channel,
+            threshold.min(0x7F),
+        ))
+    }
+}
+
+/// BUT_LD_TH, bit 7 of the recalibration configuration register
+pub(crate) fn linked_thresholds(linked: bool) -> Access {
+    Access::bit(R_RECALIBRATION, 7, linked)
+}
+
+pub(crate) fn noise_threshold(threshold: NoiseThreshold) -> Access {
+    Access::bits(R_NOISE_THRESH, 0, 2, threshold.bits())
+}
+
+/// DELTA_SENSE bits of the sensitivity control register
+pub(crate) fn sensitivity(sensitivity: Sensitivity) -> Access {
+    Access::bits(R_SENSITIVITY, 4, 3, sensitivity.bits())
+}
+
+pub(crate) fn sensitivity_from(value: u8) -> Sensitivity {
+    Sensitivity::from_bits(value >> 4)
+}
+
+/// BASE_SHIFT bits of the sensitivity control register
+pub(crate) fn base_shift(base_shift: BaseShift) -> Access {
+    Access::bits(R_SENSITIVITY, 0, 4, base_shift.bits())
+}
+
+/// Values of the threshold registers block for the first inputs
+pub(crate) fn thresholds<E>(
+    thresholds: &[u8],
+    channels: u8,
+) -> Result<ChannelValues<u8>, Error<E>> {
+    if thresholds.len() > channels as usize {
+        return Err(Error::ChannelNumberOverflowError);
+    }
+    let mut values = ChannelValues::new(thresholds.len() as u8);
+    for (value, threshold) in values.iter_mut().zip(thresholds) {
+        *value = (*threshold).min(0x7F);
+    }
+    Ok(values)
+}
+
+pub(crate) fn deltas(values: &ChannelValues<u8>) -> ChannelValues<i8> {
+    let mut deltas = ChannelValues::new(values.len() as u8);
+    for (delta, value) in deltas.iter_mut().zip(values.iter()) {
+        *delta = *value as i8;
+    }
+    deltas
+}
+
+/// Start and length of the burst read of the calibration registers, the INPUT_n_CALIB
+/// registers followed by INPUT_CAL_LSB1 and INPUT_CAL_LSB2
+pub(crate) const CALIBRATION_START: u8 = R_INPUT_1_CALIB;
+pub(crate) const CALIBRATION_LEN: usize = (R_INPUT_CAL_LSB2 - R_INPUT_1_CALIB) as usize + 1;
+
+/// 10-bit calibration value of an input: the 8 MSBs are in INPUT_n_CALIB and the 2 LSBs
+/// packed by four inputs in INPUT_CAL_LSB1 and INPUT_CAL_LSB2
+pub(crate) fn calibration_value(registers: &[u8; CALIBRATION_LEN], input: u8) -> u16 {
+    let msb = registers[input as usize];
+    let lsb_register = R_INPUT_CAL_LSB1 - CALIBRATION_START + input / 4;
+    let lsb = (registers[lsb_register as usize] >> (2 * (input % 4))) & 0b11;
+    u16::from(msb) << 2 | u16::from(lsb)
+}
+
+pub(crate) fn calibration_values(
+    registers: &[u8; CALIBRATION_LEN],
+    channel_count: u8,
+) -> ChannelValues<u16> {
+    let mut values = ChannelValues::new(channel_count);
+    for (input, value) in values.iter_mut().enumerate() {
+        *value = calibration_value(registers, input as u8);
+    }
+    values
+}
+
+pub(crate) fn diagnostics(
+    calibrations: &ChannelValues<u16>,
+    base_counts: &ChannelValues<u8>,
+) -> ChannelValues<InputDiagnostics> {
+    let mut values = ChannelValues::new(calibrations.len() as u8);
+    for (value, (calibration, base_count)) in values
+        .iter_mut()
+        .zip(calibrations.iter().zip(base_counts.iter()))
+    {
+        *value = InputDiagnostics {
+            calibration: *calibration,
+            base_count: *base_count,
+        };
+    }
+    values
+}
+
+/// Change the bit of a LED in one of the per LED registers
+pub(crate) fn led_bit<E>(
+    register: u8,
+    led_index: u8,
+    state: bool,
+    leds: u8,
+) -> Result<Access, Error<E>> {
+    if led_index >= leds {
+        Err(Error::LedNumberOverflowError)
+    } else {
+        Ok(Access::bit(register, led_index, state))
+    }
+}
+
+/// Set the output of all LEDs at once, ignoring the bits of missing LEDs
+pub(crate) fn led_states(states: u8, leds: u8) -> Access {
+    let mask = ((1u16 << leds.min(8)) - 1) as u8;
+    Access::write(R_LED_OUTPUT_CON, states & mask)
+}
+
+pub(crate) fn led_behaviour<E>(led_index: u8, value: u8, leds: u8) -> Result<Access, Error<E>> {
+    if led_index >= leds {
+        Err(Error::LedNumberOverflowError)
+    } else {
+        let offset = led_index * 2 % 8;
+        let register = led_index / 4;
+        let value = value & 3;
+        Ok(Access::bits(R_LED_BEHAVIOUR_1 + register, offset, 2, value))
+    }
+}
+
+pub(crate) fn led_period(register: u8, period: Duration) -> Access {
+    Access::bits(register, 0, 7, duration_to_period_value(period))
+}
+
+/// Number of pulses, 1 to 8, of pulse 1 (offset 0) or pulse 2 (offset 3)
+pub(crate) fn led_pulse_count<E>(offset: u8, count: u8) -> Result<Access, Error<E>> {
+    if !(1..=8).contains(&count) {
+        Err(Error::PulseCountError(count))
+    } else {
+        Ok(Access::bits(R_LED_CONFIG, offset, 3, count - 1))
+    }
+}
+
+/// RAMP_ALERT, bit 6 of the LED configuration register
+pub(crate) fn led_ramp_alert(enable: bool) -> Access {
+    Access::bit(R_LED_CONFIG, 6, enable)
+}
+
+/// Rounds the rates, in ms, down to the nearest valid value
+pub(crate) fn led_direct_ramp_rate(rise_rate: u16, fall_rate: u16) -> Access {
+    let rise_rate = rise_rate / 250;
+    let fall_rate = fall_rate / 250;
+    let rise_rate = min(7, rise_rate);
+    let fall_rate = min(7, fall_rate);
+    let rate = rise_rate << 4 | fall_rate;
+    Access::write(R_LED_DIRECT_RAMP, rate as u8)
+}
+
+pub(crate) fn led_duty(register: u8, duty_min: u8, duty_max: u8) -> Access {
+    Access::write(register, duty_max << 4 | duty_min)
+}
+
+/// Minimum duty cycle, the low nibble of one of the LED duty registers
+pub(crate) fn led_min_duty(register: u8, value: u8) -> Access {
+    Access::bits(register, 0, 4, value)
+}
+
+/// Maximum duty cycle, the high nibble of one of the LED duty registers
+pub(crate) fn led_max_duty(register: u8, value: u8) -> Access {
+    Access::bits(register, 4, 4, value)
+}
+
+/// WAKE_CFG, bit 6 of the general configuration register
+pub(crate) fn wake_on_standby_touch(value: bool) -> Access {
+    Access::bit(R_GENERAL_CONFIG, 6, value)
+}
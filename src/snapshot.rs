@@ -0,0 +1,512 @@
+//! Dump and restore of the device registers
+//!
+//! A [`RegisterSnapshot`] taken with [`CAP1XXX::snapshot`](crate::CAP1XXX::snapshot)
+//! holds every readable register of the device. Its `Display` implementation prints
+//! each register decoded, and [`CAP1XXX::restore`](crate::CAP1XXX::restore) writes the
+//! configuration back, eg. to replay a tuned configuration onto another unit.
+//!
+//! Snapshots are kept as the 256 raw register values, see
+//! [`RegisterSnapshot::as_bytes`], and are serialized as such with the `serde` feature.
+use crate::config::*;
+use crate::consts::*;
+use crate::registers;
+use crate::PowerState;
+use core::fmt;
+
+/// Ranges of readable registers, read with one burst each
+pub(crate) const READABLE: [(u8, u8); 19] = [
+    (R_MAIN_CONTROL, R_MAIN_CONTROL),
+    (R_GENERAL_STATUS, R_LED_STATUS),
+    (R_NOISE_FLAG_STATUS, R_NOISE_FLAG_STATUS),
+    (R_INPUT_1_DELTA, R_INPUT_8_DELTA),
+    (R_SENSITIVITY, R_SAMPLING_CONFIG),
+    (R_CALIBRATION, R_REPEAT_EN),
+    (R_MTOUCH_CONFIG, R_MTOUCH_PAT_CONF),
+    (R_MTOUCH_PATTERN, R_NOISE_THRESH),
+    (R_STANDBY_CHANNEL, R_CONFIGURATION2),
+    (R_INPUT_1_BCOUNT, R_INPUT_8_BCOUNT),
+    (R_POWER_BUTTON, R_POW_BUTTON_CONF),
+    (R_LED_OUTPUT_TYPE, R_LED_OUTPUT_CON),
+    (R_LED_LTRANS_CON, R_LED_LTRANS_CON),
+    (R_LED_MIRROR_CON, R_LED_MIRROR_CON),
+    (R_LED_BEHAVIOUR_1, R_LED_BEHAVIOUR_2),
+    (R_LED_PULSE_1_PER, R_LED_CONFIG),
+    (R_LED_PULSE_1_DUT, R_LED_OFF_DELAY),
+    (R_INPUT_1_CALIB, R_INPUT_CAL_LSB2),
+    (R_PRODUCT_ID, R_REVISION),
+];
+
+/// Ranges of configuration registers written back on restore
+///
+/// Status, delta and calibration registers are updated by the device and the
+/// power state bits of MAIN_CONTROL are left alone; only its gain is restored.
+pub(crate) const RESTORED: [(u8, u8); 17] = [
+    (R_SENSITIVITY, R_SAMPLING_CONFIG),
+    (R_INTERRUPT_EN, R_REPEAT_EN),
+    (R_MTOUCH_CONFIG, R_MTOUCH_CONFIG),
+    // the pattern before the detector gets enabled, as in registers::multiple_touch_pattern
+    (R_MTOUCH_PATTERN, R_MTOUCH_PATTERN),
+    (R_MTOUCH_PAT_CONF, R_MTOUCH_PAT_CONF),
+    // R_RECALIBRATION before the thresholds, which may be linked
+    (R_RECALIBRATION, R_RECALIBRATION),
+    (R_INPUT_1_THRESH, R_INPUT_8_THRESH),
+    (R_NOISE_THRESH, R_NOISE_THRESH),
+    (R_STANDBY_CHANNEL, R_CONFIGURATION2),
+    (R_POWER_BUTTON, R_POW_BUTTON_CONF),
+    (R_LED_OUTPUT_TYPE, R_LED_OUTPUT_CON),
+    (R_LED_LTRANS_CON, R_LED_LTRANS_CON),
+    (R_LED_MIRROR_CON, R_LED_MIRROR_CON),
+    (R_LED_BEHAVIOUR_1, R_LED_BEHAVIOUR_2),
+    (R_LED_PULSE_1_PER, R_LED_BREATHE_PER),
+    (R_LED_CONFIG, R_LED_CONFIG),
+    (R_LED_PULSE_1_DUT, R_LED_OFF_DELAY),
+];
+
+/// Values of all the readable registers of a device
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RegisterSnapshot {
+    registers: [u8; 256],
+}
+
+impl Default for RegisterSnapshot {
+    fn default() -> Self {
+        Self {
+            registers: [0; 256],
+        }
+    }
+}
+
+impl RegisterSnapshot {
+    /// Value of a register, 0 for registers that are not readable
+    pub fn get(&self, register: u8) -> u8 {
+        self.registers[register as usize]
+    }
+
+    /// Snapshot from the values of all the registers, indexed by address
+    pub fn from_bytes(registers: [u8; 256]) -> Self {
+        Self { registers }
+    }
+
+    /// Values of all the registers, indexed by address, eg. to store the snapshot
+    pub fn as_bytes(&self) -> &[u8; 256] {
+        &self.registers
+    }
+
+    /// Change the value of a register, eg. to tweak a configuration before restoring it
+    pub fn set(&mut self, register: u8, value: u8) {
+        self.registers[register as usize] = value;
+    }
+
+    pub(crate) fn block_mut(&mut self, start: u8, end: u8) -> &mut [u8] {
+        &mut self.registers[start as usize..=end as usize]
+    }
+
+    pub(crate) fn block(&self, start: u8, end: u8) -> &[u8] {
+        &self.registers[start as usize..=end as usize]
+    }
+
+    /// Gain, bits 7 and 6 of the main control register
+    pub(crate) fn gain_bits(&self) -> u8 {
+        self.get(R_MAIN_CONTROL) >> 6
+    }
+
+    fn block4(&self, start: u8) -> [u8; 4] {
+        let mut block = [0u8; 4];
+        block.copy_from_slice(self.block(start, start + 3));
+        block
+    }
+}
+
+#[cfg(feature = "serde")]
+impl serde::Serialize for RegisterSnapshot {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_bytes(&self.registers)
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'de> serde::Deserialize<'de> for RegisterSnapshot {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        use serde::de::{Error, SeqAccess, Visitor};
+
+        struct RegistersVisitor;
+
+        impl<'de> Visitor<'de> for RegistersVisitor {
+            type Value = RegisterSnapshot;
+
+            fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                write!(f, "the values of the 256 registers")
+            }
+
+            fn visit_bytes<E: Error>(self, bytes: &[u8]) -> Result<Self::Value, E> {
+                let mut registers = [0u8; 256];
+                if bytes.len() != registers.len() {
+                    return Err(E::invalid_length(bytes.len(), &self));
+                }
+                registers.copy_from_slice(bytes);
+                Ok(RegisterSnapshot::from_bytes(registers))
+            }
+
+            fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<Self::Value, A::Error> {
+                let mut registers = [0u8; 256];
+                for (len, value) in registers.iter_mut().enumerate() {
+                    *value = seq
+                        .next_element()?
+                        .ok_or_else(|| A::Error::invalid_length(len, &self))?;
+                }
+                if seq.next_element::<u8>()?.is_some() {
+                    return Err(A::Error::invalid_length(257, &self));
+                }
+                Ok(RegisterSnapshot::from_bytes(registers))
+            }
+        }
+
+        deserializer.deserialize_bytes(RegistersVisitor)
+    }
+}
+
+fn row(f: &mut fmt::Formatter, name: &str, register: u8, value: u8) -> fmt::Result {
+    write!(f, "{:<18} {:#04x} = {:#04x}  ", name, register, value)
+}
+
+fn bitmap(f: &mut fmt::Formatter, name: &str, register: u8, value: u8) -> fmt::Result {
+    row(f, name, register, value)?;
+    writeln!(f, "{:#010b}", value)
+}
+
+fn flag(value: u8, bit: u8) -> u8 {
+    (value >> bit) & 1
+}
+
+/// One register per line: name, address, raw value and decoded fields
+impl fmt::Display for RegisterSnapshot {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let value = self.get(R_MAIN_CONTROL);
+        row(f, "MAIN_CONTROL", R_MAIN_CONTROL, value)?;
+        writeln!(
+            f,
+            "gain {}x, {:?}, INT {}",
+            1 << (value >> 6),
+            PowerState::from_main_control(value),
+            flag(value, 0)
+        )?;
+        let value = self.get(R_GENERAL_STATUS);
+        row(f, "GENERAL_STATUS", R_GENERAL_STATUS, value)?;
+        writeln!(
+            f,
+            "BC_OUT {}, ACAL_FAIL {}, PWR {}, LED {}, MULT {}, MTP {}, TOUCH {}",
+            flag(value, 6),
+            flag(value, 5),
+            flag(value, 4),
+            flag(value, 3),
+            flag(value, 2),
+            flag(value, 1),
+            flag(value, 0)
+        )?;
+        bitmap(f, "INPUT_STATUS", R_INPUT_STATUS, self.get(R_INPUT_STATUS))?;
+        bitmap(f, "LED_STATUS", R_LED_STATUS, self.get(R_LED_STATUS))?;
+        bitmap(
+            f,
+            "NOISE_FLAG_STATUS",
+            R_NOISE_FLAG_STATUS,
+            self.get(R_NOISE_FLAG_STATUS),
+        )?;
+        for input in 0..8 {
+            let register = R_INPUT_1_DELTA + input;
+            row(f, "INPUT_DELTA", register, self.get(register))?;
+            writeln!(f, "input {}: {}", input + 1, self.get(register) as i8)?;
+        }
+
+        let value = self.get(R_SENSITIVITY);
+        row(f, "SENSITIVITY", R_SENSITIVITY, value)?;
+        writeln!(
+            f,
+            "sensitivity {}x, base shift {}x",
+            Sensitivity::from_bits(value >> 4).multiplier(),
+            BaseShift::from_bits(value).multiplier()
+        )?;
+        let value = self.get(R_GENERAL_CONFIG);
+        row(f, "GENERAL_CONFIG", R_GENERAL_CONFIG, value)?;
+        writeln!(
+            f,
+            "timeout {}, wake {}, digital noise disabled {}, analog noise disabled {}, max duration recalibration {}",
+            flag(value, 7),
+            flag(value, 6),
+            flag(value, 5),
+            flag(value, 4),
+            flag(value, 3)
+        )?;
+        bitmap(f, "INPUT_ENABLE", R_INPUT_ENABLE, self.get(R_INPUT_ENABLE))?;
+        let value = self.get(R_INPUT_CONFIG);
+        row(f, "INPUT_CONFIG", R_INPUT_CONFIG, value)?;
+        writeln!(
+            f,
+            "repeat rate {:?}",
+            crate::registers::rate_scale_to_duration(value)
+        )?;
+        let value = self.get(R_INPUT_CONFIG2);
+        row(f, "INPUT_CONFIG2", R_INPUT_CONFIG2, value)?;
+        writeln!(
+            f,
+            "hold delay {:?}",
+            crate::registers::rate_scale_to_duration(value)
+        )?;
+        let value = self.get(R_SAMPLING_CONFIG);
+        row(f, "SAMPLING_CONFIG", R_SAMPLING_CONFIG, value)?;
+        let sampling = SamplingConfig::from_register(value);
+        writeln!(
+            f,
+            "{} samples, sample time {:?}, cycle time {:?}",
+            sampling.averaging.samples(),
+            sampling.sample_time.duration(),
+            sampling.cycle_time.duration()
+        )?;
+        bitmap(f, "CALIBRATION", R_CALIBRATION, self.get(R_CALIBRATION))?;
+        bitmap(f, "INTERRUPT_EN", R_INTERRUPT_EN, self.get(R_INTERRUPT_EN))?;
+        bitmap(f, "REPEAT_EN", R_REPEAT_EN, self.get(R_REPEAT_EN))?;
+        let value = self.get(R_MTOUCH_CONFIG);
+        row(f, "MTOUCH_CONFIG", R_MTOUCH_CONFIG, value)?;
+        writeln!(
+            f,
+            "multi-touch blocked {}, limit {} touches",
+            flag(value, 7),
+            TouchLimit::from_bits(value >> 2).count()
+        )?;
+        let pattern = MultipleTouchPattern::from_registers([
+            self.get(R_MTOUCH_PAT_CONF),
+            self.get(R_MTOUCH_PATTERN),
+        ]);
+        row(
+            f,
+            "MTOUCH_PAT_CONF",
+            R_MTOUCH_PAT_CONF,
+            self.get(R_MTOUCH_PAT_CONF),
+        )?;
+        writeln!(
+            f,
+            "enabled {}, {:?} mode, threshold {}%, alert {}",
+            pattern.enabled as u8,
+            pattern.mode,
+            pattern.threshold.fraction() * 100.0,
+            pattern.alert as u8
+        )?;
+        bitmap(f, "MTOUCH_PATTERN", R_MTOUCH_PATTERN, pattern.inputs)?;
+        bitmap(
+            f,
+            "COUNT_O_LIMIT",
+            R_COUNT_O_LIMIT,
+            self.get(R_COUNT_O_LIMIT),
+        )?;
+        let value = self.get(R_RECALIBRATION);
+        row(f, "RECALIBRATION", R_RECALIBRATION, value)?;
+        writeln!(
+            f,
+            "linked thresholds {}, no clear intermediate {}, no clear negative {}, negative delta count {}, calibration config {}",
+            flag(value, 7),
+            flag(value, 6),
+            flag(value, 5),
+            (value >> 3) & 0b11,
+            value & 0b111
+        )?;
+        for input in 0..8 {
+            let register = R_INPUT_1_THRESH + input;
+            row(f, "INPUT_THRESH", register, self.get(register))?;
+            writeln!(f, "input {}: {}", input + 1, self.get(register) & 0x7F)?;
+        }
+        let value = self.get(R_NOISE_THRESH);
+        row(f, "NOISE_THRESH", R_NOISE_THRESH, value)?;
+        writeln!(
+            f,
+            "{}% of the touch threshold",
+            NoiseThreshold::from_bits(value).fraction() * 100.0
+        )?;
+
+        let standby = StandbyConfig::from_registers(self.block4(R_STANDBY_CHANNEL));
+        bitmap(f, "STANDBY_CHANNEL", R_STANDBY_CHANNEL, standby.channels)?;
+        row(
+            f,
+            "STANDBY_CONFIG",
+            R_STANDBY_CONFIG,
+            self.get(R_STANDBY_CONFIG),
+        )?;
+        writeln!(
+            f,
+            "summation {}, {} samples, sample time {:?}, cycle time {:?}",
+            standby.summation as u8,
+            standby.averaging.samples(),
+            standby.sample_time.duration(),
+            standby.cycle_time.duration()
+        )?;
+        row(f, "STANDBY_SENS", R_STANDBY_SENS, self.get(R_STANDBY_SENS))?;
+        writeln!(f, "sensitivity {}x", standby.sensitivity.multiplier())?;
+        row(
+            f,
+            "STANDBY_THRESH",
+            R_STANDBY_THRESH,
+            self.get(R_STANDBY_THRESH),
+        )?;
+        writeln!(f, "{}", standby.threshold)?;
+        let value = self.get(R_CONFIGURATION2);
+        row(f, "CONFIGURATION2", R_CONFIGURATION2, value)?;
+        writeln!(
+            f,
+            "LED transition inverted {}, ALERT active low {}, reduce power off {}, polarity/mirror unlinked {}, RF noise only {}, RF noise filter disabled {}, release interrupt disabled {}",
+            flag(value, 7),
+            flag(value, 6),
+            flag(value, 5),
+            flag(value, 4),
+            flag(value, 3),
+            flag(value, 2),
+            flag(value, 0)
+        )?;
+        for input in 0..8 {
+            let register = R_INPUT_1_BCOUNT + input;
+            row(f, "INPUT_BCOUNT", register, self.get(register))?;
+            writeln!(f, "input {}: {}", input + 1, self.get(register))?;
+        }
+        bitmap(f, "POWER_BUTTON", R_POWER_BUTTON, self.get(R_POWER_BUTTON))?;
+        bitmap(
+            f,
+            "POW_BUTTON_CONF",
+            R_POW_BUTTON_CONF,
+            self.get(R_POW_BUTTON_CONF),
+        )?;
+
+        bitmap(
+            f,
+            "LED_OUTPUT_TYPE",
+            R_LED_OUTPUT_TYPE,
+            self.get(R_LED_OUTPUT_TYPE),
+        )?;
+        bitmap(f, "LED_LINKING", R_LED_LINKING, self.get(R_LED_LINKING))?;
+        bitmap(f, "LED_POLARITY", R_LED_POLARITY, self.get(R_LED_POLARITY))?;
+        bitmap(
+            f,
+            "LED_OUTPUT_CON",
+            R_LED_OUTPUT_CON,
+            self.get(R_LED_OUTPUT_CON),
+        )?;
+        bitmap(
+            f,
+            "LED_LTRANS_CON",
+            R_LED_LTRANS_CON,
+            self.get(R_LED_LTRANS_CON),
+        )?;
+        bitmap(
+            f,
+            "LED_MIRROR_CON",
+            R_LED_MIRROR_CON,
+            self.get(R_LED_MIRROR_CON),
+        )?;
+        for (index, register) in [R_LED_BEHAVIOUR_1, R_LED_BEHAVIOUR_2].iter().enumerate() {
+            let value = self.get(*register);
+            row(f, "LED_BEHAVIOUR", *register, value)?;
+            for led in 0..4 {
+                let behaviour = match (value >> (led * 2)) & 0b11 {
+                    LED_BEHAVIOUR_DIRECT => "direct",
+                    LED_BEHAVIOUR_PULSE1 => "pulse 1",
+                    LED_BEHAVIOUR_PULSE2 => "pulse 2",
+                    _ => "breathe",
+                };
+                let separator = if led == 3 { "\n" } else { ", " };
+                write!(
+                    f,
+                    "LED {}: {}{}",
+                    index * 4 + led as usize + 1,
+                    behaviour,
+                    separator
+                )?;
+            }
+        }
+        for (name, register) in [
+            ("LED_PULSE_1_PER", R_LED_PULSE_1_PER),
+            ("LED_PULSE_2_PER", R_LED_PULSE_2_PER),
+            ("LED_BREATHE_PER", R_LED_BREATHE_PER),
+        ]
+        .iter()
+        {
+            let value = self.get(*register);
+            row(f, name, *register, value)?;
+            writeln!(f, "period {}ms", u32::from(value & 0x7F) * 32)?;
+        }
+        let value = self.get(R_LED_CONFIG);
+        row(f, "LED_CONFIG", R_LED_CONFIG, value)?;
+        writeln!(
+            f,
+            "ramp alert {}, pulse 2 count {}, pulse 1 count {}",
+            flag(value, 6),
+            ((value >> 3) & 0b111) + 1,
+            (value & 0b111) + 1
+        )?;
+        let duties = LedDuties::from_registers(self.block4(R_LED_PULSE_1_DUT));
+        for (name, register, duty) in [
+            ("LED_PULSE_1_DUT", R_LED_PULSE_1_DUT, duties.pulse1),
+            ("LED_PULSE_2_DUT", R_LED_PULSE_2_DUT, duties.pulse2),
+            ("LED_BREATHE_DUT", R_LED_BREATHE_DUT, duties.breathe),
+            ("LED_DIRECT_DUT", R_LED_DIRECT_DUT, duties.direct),
+        ]
+        .iter()
+        {
+            row(f, name, *register, self.get(*register))?;
+            writeln!(f, "min {}, max {}", duty.min, duty.max)?;
+        }
+        let value = self.get(R_LED_DIRECT_RAMP);
+        row(f, "LED_DIRECT_RAMP", R_LED_DIRECT_RAMP, value)?;
+        writeln!(
+            f,
+            "rise {}ms, fall {}ms",
+            u32::from((value >> 4) & 0b111) * 250,
+            u32::from(value & 0b111) * 250
+        )?;
+        bitmap(
+            f,
+            "LED_OFF_DELAY",
+            R_LED_OFF_DELAY,
+            self.get(R_LED_OFF_DELAY),
+        )?;
+
+        let mut calibration = [0u8; registers::CALIBRATION_LEN];
+        for (offset, value) in calibration.iter_mut().enumerate() {
+            *value = self.get(registers::CALIBRATION_START + offset as u8);
+        }
+        for input in 0..8 {
+            let register = R_INPUT_1_CALIB + input;
+            row(f, "INPUT_CALIB", register, self.get(register))?;
+            writeln!(
+                f,
+                "input {}, calibration {}",
+                input + 1,
+                registers::calibration_value(&calibration, input)
+            )?;
+        }
+        bitmap(
+            f,
+            "INPUT_CAL_LSB1",
+            R_INPUT_CAL_LSB1,
+            self.get(R_INPUT_CAL_LSB1),
+        )?;
+        bitmap(
+            f,
+            "INPUT_CAL_LSB2",
+            R_INPUT_CAL_LSB2,
+            self.get(R_INPUT_CAL_LSB2),
+        )?;
+
+        let value = self.get(R_PRODUCT_ID);
+        row(f, "PRODUCT_ID", R_PRODUCT_ID, value)?;
+        match crate::ChipModel::from_product_id(value) {
+            Some(model) => writeln!(f, "{:?}", model)?,
+            None => writeln!(f, "unknown")?,
+        }
+        let value = self.get(R_MANUFACTURER_ID);
+        row(f, "MANUFACTURER_ID", R_MANUFACTURER_ID, value)?;
+        if value == MANUFACTURER_ID_MICROCHIP {
+            writeln!(f, "Microchip")?;
+        } else {
+            writeln!(f, "unknown")?;
+        }
+        let value = self.get(R_REVISION);
+        row(f, "REVISION", R_REVISION, value)?;
+        writeln!(f, "{}", value)
+    }
+}
@@ -0,0 +1,86 @@
+//! Decoded status registers
+use crate::consts::*;
+
+/// First and last registers of the burst read by `read_status`, including the reserved
+/// registers between LED_STATUS and NOISE_FLAG_STATUS which read as 0
+pub(crate) const STATUS_START: u8 = R_GENERAL_STATUS;
+pub(crate) const STATUS_END: u8 = R_NOISE_FLAG_STATUS;
+pub(crate) const STATUS_LEN: usize = (STATUS_END - STATUS_START) as usize + 1;
+
+/// Flags of the general status register
+///
+/// The flags are latched: they are cleared when the interrupt is cleared, unless their
+/// condition is still present.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct GeneralStatus {
+    /// BC_OUT: the base count of an input is out of limit, its calibration failed
+    pub base_count_out_of_limit: bool,
+    /// ACAL_FAIL: the analog calibration of an input failed
+    pub calibration_failed: bool,
+    /// PWR: the power button has been held (cap1208 only)
+    pub power_button: bool,
+    /// LED: a LED finished its pulse or ramp (cap1166 and cap1188)
+    pub led: bool,
+    /// MULT: touches are blocked, more inputs than the touch limit are touched
+    pub multitouch_blocked: bool,
+    /// MTP: touches matching the multiple-touch pattern were detected
+    pub pattern_detected: bool,
+    /// TOUCH: an input is touched
+    pub touch: bool,
+}
+
+impl GeneralStatus {
+    pub fn from_register(value: u8) -> Self {
+        Self {
+            base_count_out_of_limit: value & 0x40 != 0,
+            calibration_failed: value & 0x20 != 0,
+            power_button: value & 0x10 != 0,
+            led: value & 0x08 != 0,
+            multitouch_blocked: value & 0x04 != 0,
+            pattern_detected: value & 0x02 != 0,
+            touch: value & 0x01 != 0,
+        }
+    }
+
+    /// Whether a calibration failed (ACAL_FAIL or BC_OUT)
+    pub fn is_calibration_error(&self) -> bool {
+        self.base_count_out_of_limit || self.calibration_failed
+    }
+}
+
+/// Calibration state of an input, to diagnose drifting or badly calibrated pads
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct InputDiagnostics {
+    /// 10-bit value chosen by the analog calibration
+    pub calibration: u16,
+    /// Base count, the reference the touch deltas are measured against
+    pub base_count: u8,
+}
+
+/// General, input, LED and noise status, read in a single burst
+///
+/// Telling apart a real touch (`inputs`), noise (`noise`) and a calibration
+/// failure (`general`) from a single read avoids races with the device updating them.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Status {
+    pub general: GeneralStatus,
+    /// Bitmap of the touched inputs
+    pub inputs: u8,
+    /// Bitmap of the LEDs that finished their pulse or ramp
+    pub leds: u8,
+    /// Bitmap of the inputs with a signal above the noise threshold but below the touch
+    /// threshold, or disturbed by RF noise
+    pub noise: u8,
+}
+
+impl Status {
+    pub(crate) fn from_registers(registers: &[u8; STATUS_LEN]) -> Self {
+        let register = |r: u8| registers[(r - STATUS_START) as usize];
+        Self {
+            general: GeneralStatus::from_register(register(R_GENERAL_STATUS)),
+            inputs: register(R_INPUT_STATUS),
+            leds: register(R_LED_STATUS),
+            noise: register(R_NOISE_FLAG_STATUS),
+        }
+    }
+}